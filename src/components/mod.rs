@@ -3,39 +3,132 @@
 use crate::math::Point;
 
 use amethyst::ecs::{Component, DenseVecStorage, Entity, WriteStorage};
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, mem};
 
 /// Tag component for the player's entity.
 #[derive(Component)]
 pub struct Player;
 
-/// Tag component for entities that can act in a turn.
-#[derive(Default, Copy, Clone, Component)]
+/// Speed of an average entity, ie. the energy it gains at every tick of the clock.
+pub const NORMAL_SPEED: u32 = 10;
+
+/// Actions that entities spend their energy on.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Action {
+    Move,
+    Attack,
+    PickUp,
+    DropItem,
+    UseItem,
+    Equip,
+    /// Opening or closing doors, and taking the stairs.
+    Interact,
+    /// Doing nothing at all for a turn.
+    Wait,
+}
+
+impl Action {
+    /// Returns the energy spent to perform the action.
+    pub fn cost(self) -> i32 {
+        match self {
+            Action::PickUp | Action::DropItem => 50,
+            Action::Equip => 150,
+            _ => 100,
+        }
+    }
+}
+
+/// Component for entities that take turns to act, as often as their speed allows.
+///
+/// At every tick of the clock, entities gain as much energy as their speed.
+/// Once their energy is no longer negative they are ready to act, and it is up to the
+/// [`TurnSystem`] to give them the turn; acting then costs them some energy.
+///
+/// [`TurnSystem`]: crate::systems::TurnSystem
+#[derive(Copy, Clone, Component, Serialize, Deserialize)]
 pub struct ActsOnTurns {
-    ap: u32,
+    pub speed: u32,
+    energy: i32,
+    turn: bool,
 }
 
 impl ActsOnTurns {
+    /// Creates an entity acting at the given speed, ready for its first turn.
+    pub fn new(speed: u32) -> ActsOnTurns {
+        ActsOnTurns {
+            speed,
+            energy: 0,
+            turn: false,
+        }
+    }
+
+    /// Returns the energy gathered so far, negative while recovering from the last action.
+    pub fn energy(self) -> i32 {
+        self.energy
+    }
+
+    /// Returns whether the entity has gathered enough energy to act.
+    pub fn is_ready(self) -> bool {
+        self.energy >= 0
+    }
+
+    /// Returns whether it is the entity's turn to act.
     pub fn can_act(self) -> bool {
-        self.ap > 0
+        self.turn
+    }
+
+    /// Adds the energy gathered while time passes.
+    pub fn gain(&mut self, energy: i32) {
+        self.energy += energy;
     }
 
-    pub fn refresh(&mut self) {
-        self.ap = 1;
+    /// Lets the entity act, until it performs an action.
+    pub fn give_turn(&mut self) {
+        self.turn = true;
     }
 
-    pub fn perform(&mut self) -> bool {
+    /// Spends the energy needed by the action, ending the turn.
+    /// Returns `false` if it was not the entity's turn to begin with.
+    pub fn perform(&mut self, action: Action) -> bool {
         if self.can_act() {
-            self.ap -= 1;
+            self.energy -= action.cost();
+            self.turn = false;
             true
         } else {
             false
         }
     }
+
+    /// Makes an entity perform an action, as long as it is its turn to act.
+    pub fn spend(store: &mut WriteStorage<ActsOnTurns>, who: Entity, action: Action) {
+        if let Some(actor) = store.get_mut(who) {
+            actor.perform(action);
+        }
+    }
+
+    /// Makes an entity spend energy for an action that took longer than usual, once it
+    /// is known how many more times its cost should be paid. The turn is left alone.
+    pub fn spend_extra(
+        store: &mut WriteStorage<ActsOnTurns>,
+        who: Entity,
+        action: Action,
+        times: u32,
+    ) {
+        if let Some(actor) = store.get_mut(who) {
+            actor.energy -= action.cost() * times as i32;
+        }
+    }
+}
+
+impl Default for ActsOnTurns {
+    fn default() -> Self {
+        ActsOnTurns::new(NORMAL_SPEED)
+    }
 }
 
 /// Tag component for an entity belonging to a faction.
-#[derive(Component, PartialEq)]
+#[derive(Clone, Component, PartialEq, Serialize, Deserialize)]
 pub struct Faction(pub u32);
 
 /// Tag component for entities that can be picked up from the ground.
@@ -47,15 +140,19 @@ pub struct Pickable;
 pub struct Consumable;
 
 /// Component for named entities.
-#[derive(Component)]
+#[derive(Clone, Component, Serialize, Deserialize)]
 pub struct Name(pub String);
 
+/// Component for entities that can be examined, holding a short description of them.
+#[derive(Clone, Component, Serialize, Deserialize)]
+pub struct Description(pub String);
+
 /// Logical position in the world map.
-#[derive(Component)]
+#[derive(Clone, Component, Serialize, Deserialize)]
 pub struct Position(pub Point);
 
 /// Component for entities that have a limited tile range.
-#[derive(Component)]
+#[derive(Clone, Component, Serialize, Deserialize)]
 pub struct Ranged {
     pub range: u32,
 }
@@ -78,18 +175,22 @@ impl Viewshed {
     }
 }
 
+/// Tag component for entities that can open closed doors by walking into them.
+#[derive(Component)]
+pub struct OpensDoors;
+
 /// Component for entities that block their tile in the world map.
 #[derive(Component)]
 pub struct BlocksTile;
 
 /// Component for entities that can heal the user for a certain amount.
-#[derive(Component)]
+#[derive(Clone, Component, Serialize, Deserialize)]
 pub struct HealsUser {
     pub amount: i32,
 }
 
 /// Component for entities that can inflict a certain amount of damage.
-#[derive(Component)]
+#[derive(Clone, Component, Serialize, Deserialize)]
 pub struct InflictsDamage {
     pub amount: i32,
 }
@@ -100,8 +201,126 @@ pub struct InBackpack {
     pub owner: Entity,
 }
 
-/// Component for entities that can participate in a fight.
+/// Slots that equipment can be worn in. Each entity can only wear one item per slot.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    Weapon,
+    Shield,
+    Armor,
+}
+
+/// Component for items that can be equipped in a slot.
+#[derive(Clone, Component, Serialize, Deserialize)]
+pub struct Equippable {
+    pub slot: EquipmentSlot,
+}
+
+/// Component for items that are currently equipped by an entity.
+///
+/// Equipped items stay in their owner's backpack.
 #[derive(Component)]
+pub struct Equipped {
+    pub owner: Entity,
+    pub slot: EquipmentSlot,
+}
+
+/// Component for equipment that increases its owner's melee power.
+#[derive(Clone, Component, Serialize, Deserialize)]
+pub struct MeleePowerBonus {
+    pub amount: i32,
+}
+
+/// Component for equipment that increases its owner's defense.
+#[derive(Clone, Component, Serialize, Deserialize)]
+pub struct DefenseBonus {
+    pub amount: i32,
+}
+
+/// Temporary effects that an entity can be under.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StatusEffect {
+    /// Moves in random directions, and is unable to fight on purpose.
+    Confusion,
+    /// Loses the given amount of HP every turn.
+    Poison(i32),
+    /// Recovers the given amount of HP every turn.
+    Regeneration(i32),
+    /// Acts twice as fast.
+    Haste,
+    /// Acts twice as slow.
+    Slow,
+}
+
+impl StatusEffect {
+    /// Returns the name of the effect, as shown to the player.
+    pub fn name(self) -> &'static str {
+        match self {
+            StatusEffect::Confusion => "Confused",
+            StatusEffect::Poison(_) => "Poisoned",
+            StatusEffect::Regeneration(_) => "Regenerating",
+            StatusEffect::Haste => "Hasted",
+            StatusEffect::Slow => "Slowed",
+        }
+    }
+
+    // Returns whether both effects are of the same kind, regardless of their strength.
+    fn same_kind(self, other: StatusEffect) -> bool {
+        mem::discriminant(&self) == mem::discriminant(&other)
+    }
+}
+
+/// A status effect, along with the number of turns it is going to last.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct ActiveEffect {
+    pub effect: StatusEffect,
+    pub turns: u32,
+}
+
+/// Component for entities under any temporary status effect.
+#[derive(Default, Clone, Component, Serialize, Deserialize)]
+pub struct StatusEffects(pub Vec<ActiveEffect>);
+
+impl StatusEffects {
+    /// Puts an entity under an effect, replacing any previous effect of the same kind.
+    pub fn apply(
+        store: &mut WriteStorage<StatusEffects>,
+        who: Entity,
+        effect: StatusEffect,
+        turns: u32,
+    ) {
+        let StatusEffects(effects) = store.entry(who).unwrap().or_insert_with(Default::default);
+
+        effects.retain(|active| !active.effect.same_kind(effect));
+        effects.push(ActiveEffect { effect, turns });
+    }
+
+    /// Returns whether an entity under these effects is confused.
+    pub fn is_confused(&self) -> bool {
+        self.0.iter().any(|a| a.effect == StatusEffect::Confusion)
+    }
+
+    /// Returns the actual speed of an entity under these effects, given its normal one.
+    pub fn speed(&self, base: u32) -> u32 {
+        self.0
+            .iter()
+            .fold(base, |speed, active| match active.effect {
+                StatusEffect::Haste => speed * 2,
+                StatusEffect::Slow => u32::max(1, speed / 2),
+                _ => speed,
+            })
+    }
+}
+
+/// Component for items that put a status effect on the user,
+/// or on anyone standing on the target location for ranged items.
+#[derive(Clone, Component, Serialize, Deserialize)]
+pub struct AppliesEffect {
+    pub effect: StatusEffect,
+    pub turns: u32,
+}
+
+/// Component for entities that can participate in a fight.
+#[derive(Clone, Component, Serialize, Deserialize)]
 pub struct CombatStats {
     pub hp: i32,
     pub max_hp: i32,
@@ -109,22 +328,143 @@ pub struct CombatStats {
     pub power: i32,
 }
 
+/// What a monster is currently up to, which decides how it spends its turns.
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AiState {
+    /// Stands still until a hostile gets close, or something hurts it.
+    Sleeping,
+    /// Roams towards a random spot of the level, if any, picking a new one once there.
+    Wandering(Option<Point>),
+    /// Chases a hostile in view, standing at the given spot.
+    Hunting(Point),
+    /// Heads to where a hostile was last seen, giving up after a few turns.
+    Searching { last_seen: Point, turns: u32 },
+    /// Runs away from the hostiles in view, while badly hurt.
+    Fleeing,
+}
+
+impl AiState {
+    /// Returns a short description of the state, as shown to the player.
+    pub fn name(self) -> &'static str {
+        match self {
+            AiState::Sleeping => "asleep",
+            AiState::Wandering(_) => "wandering",
+            AiState::Hunting(_) => "hunting",
+            AiState::Searching { .. } => "searching",
+            AiState::Fleeing => "fleeing",
+        }
+    }
+
+    /// Returns the spot the monster is heading to, if any.
+    pub fn goal(self) -> Option<Point> {
+        match self {
+            AiState::Wandering(goal) => goal,
+            AiState::Hunting(goal)
+            | AiState::Searching {
+                last_seen: goal, ..
+            } => Some(goal),
+            AiState::Sleeping | AiState::Fleeing => None,
+        }
+    }
+}
+
+/// Component for monsters, holding their AI state.
+#[derive(Clone, Component, Serialize, Deserialize)]
+pub struct Brain {
+    pub state: AiState,
+    /// Percentage of its max HP below which the monster runs away, if it ever does.
+    pub flees_below: Option<i32>,
+    hp: Option<i32>,
+}
+
+impl Brain {
+    pub fn new(state: AiState, flees_below: Option<i32>) -> Brain {
+        Brain {
+            state,
+            flees_below,
+            hp: None,
+        }
+    }
+
+    /// Returns whether the monster has lost any HP since the last time it checked.
+    pub fn was_hurt(&mut self, hp: i32) -> bool {
+        let hurt = self.hp.map(|last| hp < last).unwrap_or(false);
+        self.hp = Some(hp);
+        hurt
+    }
+
+    /// Returns whether the monster is hurt badly enough to run away.
+    pub fn should_flee(&self, stats: &CombatStats) -> bool {
+        self.flees_below
+            .map(|percent| stats.hp * 100 < stats.max_hp * percent)
+            .unwrap_or(false)
+    }
+}
+
+/// Component for entities that grow stronger by gaining experience.
+///
+/// Experience points only count the progress towards the next level,
+/// and start over from zero at each level up.
+#[derive(Clone, Component, Serialize, Deserialize)]
+pub struct Experience {
+    pub level: u32,
+    pub xp: u32,
+}
+
+impl Experience {
+    /// Returns the experience points needed to go from the current level to the next.
+    pub fn next_level(&self) -> u32 {
+        50 * self.level
+    }
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Experience { level: 1, xp: 0 }
+    }
+}
+
+/// Component for entities that award experience points to whoever kills them.
+#[derive(Clone, Component, Serialize, Deserialize)]
+pub struct AwardsExperience {
+    pub amount: u32,
+}
+
 /// Component for entities that have decided to move in their turn.
 #[derive(Component)]
 pub struct WantsToMove {
     pub to: Point,
 }
 
+/// Component for entities moving on their own over several turns, until something stops them.
+#[derive(Component)]
+pub struct AutoMove {
+    pub goal: AutoMoveGoal,
+    /// Hit points at the previous step, so that any damage taken can stop the movement.
+    pub hp: i32,
+    /// Position at the previous step.
+    pub from: Point,
+}
+
+/// Where an entity with [`AutoMove`] is heading to.
+pub enum AutoMoveGoal {
+    /// Towards the nearest tile that has not been revealed yet.
+    Explore,
+    /// Along a path, whose points are consumed as they are reached.
+    Travel(Vec<Point>),
+}
+
 /// Component for entities that want to pick up a `Pickable` entity.
 #[derive(Component)]
 pub struct WantsToPickUp {
     pub what: Entity,
 }
 
-/// Component for entities that have decided to use an item.
+/// Component for entities that have decided to use an item, optionally on a target location.
 #[derive(Component)]
 pub struct WantsToUseItem {
     pub what: Entity,
+    pub target: Option<Point>,
 }
 
 /// Component for entities that have decided to drop an item.
@@ -133,6 +473,18 @@ pub struct WantsToDropItem {
     pub what: Entity,
 }
 
+/// Component for entities that have decided to equip an item.
+#[derive(Component)]
+pub struct WantsToEquip {
+    pub what: Entity,
+}
+
+/// Component for entities that have decided to take off an equipped item.
+#[derive(Component)]
+pub struct WantsToUnequip {
+    pub what: Entity,
+}
+
 /// Component for entities that are being targeted by another entity for melee combat.
 #[derive(Default, Component)]
 pub struct TargetedForMelee {
@@ -152,18 +504,41 @@ impl TargetedForMelee {
 }
 
 /// Component for entities that have to suffer an amout of damage.
+///
+/// Damage is dealt either by other entities, or by hazards such as lava, in which case
+/// only a description of the `cause` is kept.
 #[derive(Default, Component)]
 pub struct SuffersDamage {
     pub damage: u32,
+    pub by: Vec<Entity>,
+    pub cause: Option<String>,
 }
 
 impl SuffersDamage {
-    /// Adds some damage to the total suffered by an entity.
-    pub fn damage(store: &mut WriteStorage<SuffersDamage>, who: Entity, amount: u32) {
-        store
+    /// Adds some damage dealt by `by` to the total suffered by an entity.
+    pub fn damage(store: &mut WriteStorage<SuffersDamage>, who: Entity, amount: u32, by: Entity) {
+        let entry = store
             .entry(who)
             .unwrap()
-            .or_insert(SuffersDamage::default())
-            .damage += amount;
+            .or_insert(SuffersDamage::default());
+
+        entry.damage += amount;
+        entry.by.push(by);
+    }
+
+    /// Adds some damage dealt by a hazard to the total suffered by an entity.
+    pub fn hazard<S: ToString>(
+        store: &mut WriteStorage<SuffersDamage>,
+        who: Entity,
+        amount: u32,
+        cause: S,
+    ) {
+        let entry = store
+            .entry(who)
+            .unwrap()
+            .or_insert(SuffersDamage::default());
+
+        entry.damage += amount;
+        entry.cause = Some(cause.to_string());
     }
 }
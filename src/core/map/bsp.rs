@@ -0,0 +1,112 @@
+use super::{Level, MapBuilder, WorldMap};
+use crate::math::{Point, Rect};
+
+use rand::{Rng, RngCore};
+
+/// Dungeon generator based on binary space partitioning.
+///
+/// The map is recursively split into smaller partitions until they are too small to be split
+/// any further. A room is then placed in each leaf partition, and sibling partitions are
+/// connected to each other on the way back up, so that the whole dungeon is connected.
+pub struct BspDungeon {
+    pub min_partition: u32,
+    pub min_room: u32,
+}
+
+impl Default for BspDungeon {
+    fn default() -> Self {
+        BspDungeon {
+            min_partition: 10,
+            min_room: 5,
+        }
+    }
+}
+
+impl MapBuilder for BspDungeon {
+    fn build(&self, width: u32, height: u32, rng: &mut dyn RngCore) -> Level {
+        let mut map = WorldMap::new(width, height);
+
+        // Leave the outer border of the map untouched
+        self.partition(Rect::new(1, 1, width - 2, height - 2), &mut map, rng);
+
+        map.place_doors();
+
+        // The player starts in the first room, while all the others get populated
+        Level {
+            start: map.rooms[0].center(),
+            spawn_regions: map.rooms[1..].iter().map(Rect::inner_points).collect(),
+            map,
+        }
+    }
+}
+
+impl BspDungeon {
+    // Recursively splits an area and fills it with rooms.
+    // Returns a point inside one of the rooms, to which the sibling partition can connect.
+    fn partition(&self, area: Rect, map: &mut WorldMap, rng: &mut dyn RngCore) -> Point {
+        let can_split_x = area.width() >= 2 * self.min_partition;
+        let can_split_y = area.height() >= 2 * self.min_partition;
+
+        let split_x = match (can_split_x, can_split_y) {
+            (false, false) => return self.place_room(area, map, rng),
+            (true, true) => rng.gen::<bool>(),
+            (split_x, _) => split_x,
+        };
+
+        let (first, second) = if split_x {
+            let w = rng.gen_range(self.min_partition, area.width() - self.min_partition + 1);
+            (
+                Rect::new(area.left(), area.bottom(), w, area.height()),
+                Rect::new(
+                    area.left() + w,
+                    area.bottom(),
+                    area.width() - w,
+                    area.height(),
+                ),
+            )
+        } else {
+            let h = rng.gen_range(self.min_partition, area.height() - self.min_partition + 1);
+            (
+                Rect::new(area.left(), area.bottom(), area.width(), h),
+                Rect::new(
+                    area.left(),
+                    area.bottom() + h,
+                    area.width(),
+                    area.height() - h,
+                ),
+            )
+        };
+
+        let p1 = self.partition(first, map, rng);
+        let p2 = self.partition(second, map, rng);
+
+        // Join the two halves together
+        if rng.gen::<bool>() {
+            map.create_horizontal_corridor(p1.x(), p2.x(), p1.y());
+            map.create_vertical_corridor(p1.y(), p2.y(), p2.x());
+        } else {
+            map.create_vertical_corridor(p1.y(), p2.y(), p1.x());
+            map.create_horizontal_corridor(p1.x(), p2.x(), p2.y());
+        }
+
+        if rng.gen::<bool>() {
+            p1
+        } else {
+            p2
+        }
+    }
+
+    // Places a randomly sized room inside a leaf partition and returns its center.
+    fn place_room(&self, area: Rect, map: &mut WorldMap, rng: &mut dyn RngCore) -> Point {
+        let w = rng.gen_range(self.min_room, area.width() + 1);
+        let h = rng.gen_range(self.min_room, area.height() + 1);
+        let x = rng.gen_range(area.left(), area.right() - w + 2);
+        let y = rng.gen_range(area.bottom(), area.top() - h + 2);
+
+        let room = Rect::new(x, y, w, h);
+        map.create_room(&room);
+        map.rooms.push(room);
+
+        room.center()
+    }
+}
@@ -0,0 +1,129 @@
+use super::{square_regions, Level, MapBuilder, TileKind, WorldMap};
+use crate::math::{self, Point};
+
+use rand::{Rng, RngCore};
+use std::collections::HashSet;
+
+/// Smallest fraction of the map that the cave must cover, or it is thrown away and dug anew.
+const MIN_FLOOR: f64 = 0.25;
+
+/// Cave generator based on cellular automata.
+///
+/// The map is first filled with random noise, which is then smoothed into organic-looking
+/// caverns by repeatedly applying the "4-5 rule": a tile becomes a wall if at least five
+/// of its neighbours are walls. Finally, only the largest cave is kept, and every other
+/// pocket is filled in. Maps whose largest cave is too small are discarded altogether.
+pub struct CellularCaves {
+    pub wall_chance: f64,
+    pub iterations: usize,
+    pub region_size: u32,
+}
+
+impl Default for CellularCaves {
+    fn default() -> Self {
+        CellularCaves {
+            wall_chance: 0.45,
+            iterations: 12,
+            region_size: 10,
+        }
+    }
+}
+
+impl MapBuilder for CellularCaves {
+    fn build(&self, width: u32, height: u32, rng: &mut dyn RngCore) -> Level {
+        loop {
+            let mut map = self.dig(width, height, rng);
+            let cave = largest_cave(&map);
+
+            if (cave.len() as f64) < MIN_FLOOR * f64::from(width * height) {
+                continue;
+            }
+
+            // Start as close as possible to the center, and drop everything else
+            let center = Point::new(width / 2, height / 2);
+            let start = cave
+                .into_iter()
+                .min_by_key(|&p| math::distance_2d(p, center))
+                .unwrap();
+
+            map.remove_unreachable(start);
+
+            return Level {
+                spawn_regions: square_regions(&map, start, self.region_size),
+                start,
+                map,
+            };
+        }
+    }
+}
+
+impl CellularCaves {
+    // Fills the map with noise and smooths it into caves.
+    fn dig(&self, width: u32, height: u32, rng: &mut dyn RngCore) -> WorldMap {
+        let mut map = WorldMap::new(width, height);
+
+        // Seed random walls, keeping the outer border solid
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                if !rng.gen_bool(self.wall_chance) {
+                    let idx = map.xy_to_idx(x, y);
+                    map.tiles[idx].kind = TileKind::Floor;
+                }
+            }
+        }
+
+        for _ in 0..self.iterations {
+            map = self.smooth(&map);
+        }
+
+        map
+    }
+
+    // Runs one iteration of the automaton over the whole map.
+    fn smooth(&self, map: &WorldMap) -> WorldMap {
+        let mut next = map.clone();
+
+        for y in 1..map.height - 1 {
+            for x in 1..map.width - 1 {
+                let p = Point::new(x, y);
+
+                let walls = map
+                    .neighbours(p)
+                    .into_iter()
+                    .filter(|&n| map[n].kind == TileKind::Wall)
+                    .count();
+
+                next[p].kind = if walls >= 5 || walls == 0 {
+                    TileKind::Wall
+                } else {
+                    TileKind::Floor
+                };
+            }
+        }
+
+        next
+    }
+}
+
+// Returns all the tiles of the largest connected cave, which is empty if there is no floor at all.
+fn largest_cave(map: &WorldMap) -> Vec<Point> {
+    let mut visited = HashSet::new();
+    let mut largest = Vec::new();
+
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let p = Point::new(x, y);
+            if map[p].kind != TileKind::Floor || visited.contains(&p) {
+                continue;
+            }
+
+            let cave = map.reachable_from(p);
+            visited.extend(cave.iter().copied());
+            if cave.len() > largest.len() {
+                largest = cave;
+            }
+        }
+    }
+
+    largest
+}
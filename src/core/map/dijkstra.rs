@@ -0,0 +1,130 @@
+use super::{TileKind, WorldMap, ADJACENT_DELTAS};
+use crate::math::Point;
+
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+/// Distance field over the whole map, computed from one or many goal points.
+///
+/// Each tile holds the cost of the cheapest path from there to the closest goal,
+/// so that walking downhill leads to a goal, while walking uphill leads away from all of them.
+/// Unlike [`a_star_search`], a single map can be shared by any number of entities.
+///
+/// [`a_star_search`]: super::a_star_search
+pub struct DijkstraMap {
+    width: u32,
+    height: u32,
+    distances: Vec<Option<u32>>,
+}
+
+impl DijkstraMap {
+    /// Computes the distance of every tile from the closest of the given goals.
+    ///
+    /// Moving onto a tile costs as much as walking on its terrain. Tiles blocked by entities
+    /// get a distance, but paths do not go through them, so that they flow around crowds.
+    /// If `opens_doors` is set, paths can also go through closed doors.
+    pub fn new(map: &WorldMap, goals: &[Point], opens_doors: bool) -> DijkstraMap {
+        let mut dm = DijkstraMap {
+            width: map.width(),
+            height: map.height(),
+            distances: vec![None; (map.width() * map.height()) as usize],
+        };
+
+        let mut queue = BinaryHeap::new();
+        for &goal in goals.iter().filter(|&&p| map.contains(p)) {
+            let idx = dm.pt_to_idx(goal);
+            dm.distances[idx] = Some(0);
+            queue.push(Reverse((0, idx)));
+        }
+
+        let can_enter = |kind: TileKind| {
+            kind.walk_cost().is_some() && (opens_doors || kind != TileKind::ClosedDoor)
+        };
+
+        while let Some(Reverse((dist, idx))) = queue.pop() {
+            // Skip stale entries, which have been superseded by a shorter path
+            if dm.distances[idx].map(|best| dist > best).unwrap_or(false) {
+                continue;
+            }
+
+            let p = dm.idx_to_pt(idx);
+            let tile = map[p];
+
+            // Paths end on blocked tiles, unless they are goals or doors to be opened
+            let crossable = !tile.blocked || tile.kind == TileKind::ClosedDoor;
+            if dist > 0 && !crossable {
+                continue;
+            }
+
+            // Walking from a neighbour to this tile costs as much as this tile's terrain
+            let cost = tile.kind.walk_cost().unwrap_or(1);
+
+            for n in map.neighbours(p) {
+                let n_idx = dm.pt_to_idx(n);
+                let next = dist + cost;
+
+                if can_enter(map[n].kind) && dm.distances[n_idx].map(|d| next < d).unwrap_or(true) {
+                    dm.distances[n_idx] = Some(next);
+                    queue.push(Reverse((next, n_idx)));
+                }
+            }
+        }
+
+        dm
+    }
+
+    /// Returns the distance from the given point to the closest goal, if it can be reached.
+    pub fn get(&self, p: Point) -> Option<u32> {
+        if p.x() < self.width && p.y() < self.height {
+            self.distances[self.pt_to_idx(p)]
+        } else {
+            None
+        }
+    }
+
+    /// Returns the adjacent tile which leads closer to the goals, if any.
+    pub fn downhill(&self, map: &WorldMap, from: Point) -> Option<Point> {
+        let here = self.get(from).unwrap_or(u32::max_value());
+
+        self.steps(map, from)
+            .filter(|&(_, d)| d < here)
+            .min_by_key(|&(_, d)| d)
+            .map(|(p, _)| p)
+    }
+
+    /// Returns the adjacent tile which leads farther away from the goals, if any.
+    ///
+    /// Walking uphill greedily can end up in a dead end, so this is better suited
+    /// for running away for a few turns than for actually going anywhere.
+    pub fn uphill(&self, map: &WorldMap, from: Point) -> Option<Point> {
+        let here = self.get(from).unwrap_or(0);
+
+        self.steps(map, from)
+            .filter(|&(_, d)| d > here)
+            .max_by_key(|&(_, d)| d)
+            .map(|(p, _)| p)
+    }
+
+    // Returns the tiles that can be stepped on from `from`, along with their distance.
+    // Goals are always included, even when blocked, so that their occupants can be reached.
+    fn steps<'a>(
+        &'a self,
+        map: &'a WorldMap,
+        from: Point,
+    ) -> impl Iterator<Item = (Point, u32)> + 'a {
+        ADJACENT_DELTAS
+            .iter()
+            .map(move |&(dx, dy)| (from.x() as i32 + dx, from.y() as i32 + dy))
+            .filter(|&(x, y)| x >= 0 && y >= 0)
+            .map(|(x, y)| Point::new(x as u32, y as u32))
+            .filter_map(move |p| self.get(p).map(|d| (p, d)))
+            .filter(move |&(p, d)| d == 0 || !map[p].blocked || map[p].kind == TileKind::ClosedDoor)
+    }
+
+    fn pt_to_idx(&self, p: Point) -> usize {
+        (p.y() * self.width + p.x()) as usize
+    }
+
+    fn idx_to_pt(&self, idx: usize) -> Point {
+        Point::new(idx as u32 % self.width, idx as u32 / self.width)
+    }
+}
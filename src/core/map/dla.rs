@@ -0,0 +1,67 @@
+use super::{square_regions, Level, MapBuilder, WorldMap};
+use crate::math::Point;
+
+use rand::{Rng, RngCore};
+
+/// Cavern generator based on diffusion-limited aggregation.
+///
+/// Starting from a small seed area around the player's position, particles are released
+/// at random points of the map and wander around until they bump into the dug out area,
+/// at which point they stick to it. This grows branching, coral-like caverns which are
+/// connected to the start by construction.
+pub struct DiffusionLimitedAggregation {
+    pub floor_percent: f32,
+    pub max_steps: u32,
+    pub region_size: u32,
+}
+
+impl Default for DiffusionLimitedAggregation {
+    fn default() -> Self {
+        DiffusionLimitedAggregation {
+            floor_percent: 0.3,
+            max_steps: 5000,
+            region_size: 10,
+        }
+    }
+}
+
+impl MapBuilder for DiffusionLimitedAggregation {
+    fn build(&self, width: u32, height: u32, rng: &mut dyn RngCore) -> Level {
+        let mut map = WorldMap::new(width, height);
+
+        // Dig out the seed area
+        let start = Point::new(width / 2, height / 2);
+        for p in map.neighbours(start) {
+            map.carve(p);
+        }
+        map.carve(start);
+
+        let target = (self.floor_percent * (width * height) as f32) as usize;
+        let mut floors = 9;
+
+        while floors < target {
+            let mut p = Point::new(rng.gen_range(1, width - 1), rng.gen_range(1, height - 1));
+
+            // Wander around until the next step would end up on the dug out area.
+            // Particles which wander for too long are simply discarded.
+            for _ in 0..self.max_steps {
+                let next = map.random_step(p, rng);
+
+                if map[next].kind.is_walkable() {
+                    if map.carve(p) {
+                        floors += 1;
+                    }
+                    break;
+                }
+
+                p = next;
+            }
+        }
+
+        Level {
+            spawn_regions: square_regions(&map, start, self.region_size),
+            start,
+            map,
+        }
+    }
+}
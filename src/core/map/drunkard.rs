@@ -0,0 +1,65 @@
+use super::{square_regions, Level, MapBuilder, WorldMap};
+use crate::math::Point;
+
+use rand::{Rng, RngCore};
+
+/// Cavern generator based on "drunkard's walk".
+///
+/// Walkers stumble around the map in random directions, digging tunnels wherever they go.
+/// The first walker starts at the player's position, and each of the following ones starts
+/// from a random tile that has already been dug out, so every tunnel is connected to the
+/// start. Walkers keep being spawned until enough of the map has been dug out.
+pub struct DrunkardsWalk {
+    pub max_walkers: usize,
+    pub lifetime: u32,
+    pub floor_percent: f32,
+    pub region_size: u32,
+}
+
+impl Default for DrunkardsWalk {
+    fn default() -> Self {
+        DrunkardsWalk {
+            max_walkers: 500,
+            lifetime: 400,
+            floor_percent: 0.5,
+            region_size: 10,
+        }
+    }
+}
+
+impl MapBuilder for DrunkardsWalk {
+    fn build(&self, width: u32, height: u32, rng: &mut dyn RngCore) -> Level {
+        let mut map = WorldMap::new(width, height);
+
+        let start = Point::new(width / 2, height / 2);
+        map.carve(start);
+
+        let target = (self.floor_percent * (width * height) as f32) as usize;
+        let mut floors = vec![start];
+
+        for walker in 0..self.max_walkers {
+            if floors.len() >= target {
+                break;
+            }
+
+            let mut p = if walker == 0 {
+                start
+            } else {
+                floors[rng.gen_range(0, floors.len())]
+            };
+
+            for _ in 0..self.lifetime {
+                if map.carve(p) {
+                    floors.push(p);
+                }
+                p = map.random_step(p, rng);
+            }
+        }
+
+        Level {
+            spawn_regions: square_regions(&map, start, self.region_size),
+            start,
+            map,
+        }
+    }
+}
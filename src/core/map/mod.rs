@@ -0,0 +1,731 @@
+//! Core map structure and handling.
+
+mod bsp;
+mod cellular;
+mod dijkstra;
+mod dla;
+mod drunkard;
+mod rooms;
+
+pub use bsp::*;
+pub use cellular::*;
+pub use dijkstra::*;
+pub use dla::*;
+pub use drunkard::*;
+pub use rooms::*;
+
+use crate::math::{self, Point, Rect};
+
+use rand::{seq::SliceRandom, Rng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet, VecDeque},
+    ops::{Index, IndexMut},
+};
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TileKind {
+    Wall,
+    Floor,
+    DownStairs,
+    UpStairs,
+    ClosedDoor,
+    OpenDoor,
+    ShallowWater,
+    DeepWater,
+    Lava,
+    Rubble,
+    TallGrass,
+}
+
+impl Default for TileKind {
+    fn default() -> Self {
+        TileKind::Wall
+    }
+}
+
+impl TileKind {
+    /// Returns a human readable name for this kind of tile.
+    pub fn name(self) -> &'static str {
+        match self {
+            TileKind::Wall => "Wall",
+            TileKind::Floor => "Floor",
+            TileKind::DownStairs => "Stairs leading down",
+            TileKind::UpStairs => "Stairs leading up",
+            TileKind::ClosedDoor => "Closed door",
+            TileKind::OpenDoor => "Open door",
+            TileKind::ShallowWater => "Shallow water",
+            TileKind::DeepWater => "Deep water",
+            TileKind::Lava => "Lava",
+            TileKind::Rubble => "Rubble",
+            TileKind::TallGrass => "Tall grass",
+        }
+    }
+
+    /// Returns whether a player can walk on this tile.
+    ///
+    /// Nobody can swim yet, so deep water is just as impassable as a wall.
+    pub fn is_walkable(self) -> bool {
+        match self {
+            TileKind::Wall | TileKind::ClosedDoor | TileKind::DeepWater => false,
+            _ => true,
+        }
+    }
+
+    /// Returns whether an entity can see through this tile.
+    pub fn is_solid(self) -> bool {
+        match self {
+            TileKind::Wall | TileKind::ClosedDoor | TileKind::TallGrass => true,
+            _ => false,
+        }
+    }
+
+    /// Returns whether this tile can be walked on, possibly after opening it.
+    pub fn is_passable(self) -> bool {
+        self.is_walkable() || self == TileKind::ClosedDoor
+    }
+
+    /// Returns whether monsters and items can be placed on this tile when populating a level.
+    pub fn is_spawnable(self) -> bool {
+        match self {
+            TileKind::Floor | TileKind::ShallowWater | TileKind::Rubble | TileKind::TallGrass => {
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the cost of moving onto this tile, or `None` if it cannot be walked on.
+    ///
+    /// Closed doors have to be opened first, which adds to their cost.
+    /// Lava is passable, but so costly that it is only crossed when there is no way around.
+    pub fn walk_cost(self) -> Option<u32> {
+        match self {
+            TileKind::Wall | TileKind::DeepWater => None,
+            TileKind::ShallowWater => Some(2),
+            TileKind::ClosedDoor | TileKind::Rubble => Some(3),
+            TileKind::Lava => Some(20),
+            _ => Some(1),
+        }
+    }
+}
+
+/// Internal state of a map tile.
+#[derive(Default, Copy, Clone, Serialize, Deserialize)]
+pub struct TileState {
+    pub kind: TileKind,
+    pub revealed: bool,
+    pub visible: bool,
+    pub blocked: bool,
+}
+
+/// A procedurally generated level, as produced by a [`MapBuilder`].
+pub struct Level {
+    pub map: WorldMap,
+    pub start: Point,
+    pub spawn_regions: Vec<Vec<Point>>,
+}
+
+/// Common interface of all the map generation algorithms.
+pub trait MapBuilder {
+    /// Generates a new level of the given size.
+    ///
+    /// Besides the map itself, builders decide where the player starts and which areas
+    /// of the map can be populated with monsters and items.
+    fn build(&self, width: u32, height: u32, rng: &mut dyn RngCore) -> Level;
+}
+
+/// Returns the builder to be used for the next level.
+pub fn random_builder(rng: &mut dyn RngCore) -> Box<dyn MapBuilder> {
+    match rng.gen_range(0, 5) {
+        0 => Box::new(RoomsAndCorridors::default()),
+        1 => Box::new(BspDungeon::default()),
+        2 => Box::new(CellularCaves::default()),
+        3 => Box::new(DrunkardsWalk::default()),
+        _ => Box::new(DiffusionLimitedAggregation::default()),
+    }
+}
+
+/// Generates a new level of the given size using a builder chosen by [`random_builder`].
+///
+/// The way down to the next level is placed as far away as possible from the player.
+/// Levels below the first one also have a way back up, right where the player starts.
+pub fn generate_level(width: u32, height: u32, depth: u32, rng: &mut dyn RngCore) -> Level {
+    let mut level = random_builder(rng).build(width, height, rng);
+
+    // Tiles are reached in breadth-first order, so the last one is also the farthest
+    if let Some(&exit) = level.map.reachable_from(level.start).last() {
+        assert_ne!(exit, level.start, "level without anywhere to go");
+        level.map[exit].kind = TileKind::DownStairs;
+    }
+
+    if depth > 1 {
+        level.map[level.start].kind = TileKind::UpStairs;
+    }
+
+    scatter_terrain(&mut level.map, level.start, rng);
+
+    // Terrain may have covered some of the spawn points with something nasty
+    let map = &level.map;
+    for region in &mut level.spawn_regions {
+        region.retain(|&p| map[p].kind.is_spawnable());
+    }
+
+    level.map.reload_blocked_tiles();
+    level
+}
+
+/// Covers random patches of floor with special terrain, such as water or grass.
+///
+/// Hazardous patches are never allowed to cut off any part of the level from `start`,
+/// so that crossing them is never mandatory.
+fn scatter_terrain(map: &mut WorldMap, start: Point, rng: &mut dyn RngCore) {
+    const PATCHES: usize = 10;
+    const PATCH_STEPS: usize = 16;
+
+    const TERRAINS: [TileKind; 5] = [
+        TileKind::ShallowWater,
+        TileKind::DeepWater,
+        TileKind::Lava,
+        TileKind::Rubble,
+        TileKind::TallGrass,
+    ];
+
+    let floor = map
+        .reachable_from(start)
+        .into_iter()
+        .filter(|&p| p != start && map[p].kind == TileKind::Floor)
+        .collect::<Vec<_>>();
+
+    for _ in 0..PATCHES {
+        let (kind, mut p) = match (TERRAINS.choose(rng), floor.choose(rng)) {
+            (Some(&kind), Some(&p)) => (kind, p),
+            _ => return,
+        };
+
+        // Earlier patches might have covered this spot already
+        if map[p].kind != TileKind::Floor {
+            continue;
+        }
+
+        // Grow the patch with a random walk over the floor
+        let mut patch = Vec::new();
+        for _ in 0..PATCH_STEPS {
+            if !patch.contains(&p) {
+                patch.push(p);
+            }
+
+            let next = map.random_step(p, rng);
+            if next != start && map[next].kind == TileKind::Floor {
+                p = next;
+            }
+        }
+
+        let hazardous = kind == TileKind::DeepWater || kind == TileKind::Lava;
+        if hazardous {
+            // Try the patch out as a wall first, and give up if anything gets cut off
+            let before = map.reachable_from(start).len();
+            for &p in &patch {
+                map[p].kind = TileKind::Wall;
+            }
+
+            if map.reachable_from(start).len() + patch.len() < before {
+                for &p in &patch {
+                    map[p].kind = TileKind::Floor;
+                }
+                continue;
+            }
+        }
+
+        for &p in &patch {
+            map[p].kind = kind;
+        }
+    }
+}
+
+/// Splits the walkable tiles reachable from `start` into square regions of the given size.
+///
+/// This is meant for maps which have no rooms to populate. The region around `start`
+/// is left out, and so are regions too small to be worth populating.
+fn square_regions(map: &WorldMap, start: Point, size: u32) -> Vec<Vec<Point>> {
+    const MIN_REGION_SIZE: usize = 8;
+
+    // A `BTreeMap` keeps the regions in a deterministic order
+    let mut regions = BTreeMap::new();
+    for p in map.reachable_from(start) {
+        regions
+            .entry((p.y() / size, p.x() / size))
+            .or_insert_with(Vec::new)
+            .push(p);
+    }
+
+    let start_region = (start.y() / size, start.x() / size);
+
+    regions
+        .into_iter()
+        .filter(|(key, region)| *key != start_region && region.len() >= MIN_REGION_SIZE)
+        .map(|(_, region)| region)
+        .collect()
+}
+
+/// Offsets of the tiles adjacent to any given tile.
+///
+/// Note: this order affects the paths returned by the A* algorithm.
+/// Keep the cardinal positions first, to avoid glitchy side movements.
+const ADJACENT_DELTAS: [(i32, i32); 8] = [
+    (0, 1),
+    (1, 0),
+    (0, -1),
+    (-1, 0),
+    (1, 1),
+    (1, -1),
+    (-1, -1),
+    (-1, 1),
+];
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct WorldMap {
+    width: u32,
+    height: u32,
+    rooms: Vec<Rect>,
+    tiles: Vec<TileState>,
+}
+
+impl WorldMap {
+    /// Creates a new map of the given size, entirely made of walls.
+    pub fn new(width: u32, height: u32) -> WorldMap {
+        WorldMap {
+            width,
+            height,
+            rooms: Vec::new(),
+            tiles: vec![TileState::default(); (width * height) as usize],
+        }
+    }
+
+    /// Returns the map's width, ie. the number of columns.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the map's height, ie. the number of rows.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the tile at the given point, if present.
+    pub fn get(&self, p: Point) -> Option<TileKind> {
+        self.tiles.get(self.pt_to_idx(p)).map(|t| t.kind)
+    }
+
+    /// Returns the first tile of the given kind, if any.
+    pub fn find(&self, kind: TileKind) -> Option<Point> {
+        self.tiles
+            .iter()
+            .position(|t| t.kind == kind)
+            .map(|idx| Point::new(idx as u32 % self.width, idx as u32 / self.width))
+    }
+
+    /// Populates blocked tiles in the map to their default values.
+    pub fn reload_blocked_tiles(&mut self) {
+        for t in self.tiles.iter_mut() {
+            t.blocked = !t.kind.is_walkable();
+        }
+    }
+
+    /// Sets all tiles as not visible.
+    pub fn clear_visibility(&mut self) {
+        for t in self.tiles.iter_mut() {
+            t.visible = false;
+        }
+    }
+
+    /// Computes all the walkable adjacent positions.
+    ///
+    /// Adjacency is computed on both cardinal intercardinal points.
+    pub fn get_adjacent_exits(&self, p: Point) -> Vec<Point> {
+        ADJACENT_DELTAS
+            .iter()
+            .filter_map(|&delta| {
+                let p = p.translate(delta.0, delta.1);
+                if !self[p].blocked {
+                    return Some(p);
+                }
+                None
+            })
+            .collect()
+    }
+
+    /// Computes all the adjacent positions holding a closed door.
+    pub fn get_adjacent_doors(&self, p: Point) -> Vec<Point> {
+        ADJACENT_DELTAS
+            .iter()
+            .map(|&delta| p.translate(delta.0, delta.1))
+            .filter(|&p| self[p].kind == TileKind::ClosedDoor)
+            .collect()
+    }
+
+    /// Returns the a reference to the rooms in this map.
+    pub fn rooms(&self) -> &[Rect] {
+        &self.rooms
+    }
+
+    /// Returns whether the point lies within the map.
+    pub fn contains(&self, p: Point) -> bool {
+        p.x() < self.width && p.y() < self.height
+    }
+
+    /// Returns all the passable tiles that can be reached from `start`, including itself.
+    ///
+    /// Only the terrain is taken into account, not the tiles blocked by entities.
+    /// Closed doors do not stop the search, as they can be opened.
+    pub fn reachable_from(&self, start: Point) -> Vec<Point> {
+        let mut visited = vec![false; self.tiles.len()];
+        let mut queue = VecDeque::new();
+        let mut reachable = Vec::new();
+
+        visited[self.pt_to_idx(start)] = true;
+        queue.push_back(start);
+
+        while let Some(p) = queue.pop_front() {
+            reachable.push(p);
+
+            for n in self.neighbours(p) {
+                let idx = self.pt_to_idx(n);
+                if !visited[idx] && self.tiles[idx].kind.is_passable() {
+                    visited[idx] = true;
+                    queue.push_back(n);
+                }
+            }
+        }
+
+        reachable
+    }
+
+    // Returns all the points around `p` which lie within the map.
+    fn neighbours(&self, p: Point) -> Vec<Point> {
+        let (x, y) = (p.x() as i32, p.y() as i32);
+
+        (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| (x + dx, y + dy)))
+            .filter(|&(nx, ny)| {
+                (nx, ny) != (x, y)
+                    && nx >= 0
+                    && ny >= 0
+                    && nx < self.width as i32
+                    && ny < self.height as i32
+            })
+            .map(|(nx, ny)| Point::new(nx as u32, ny as u32))
+            .collect()
+    }
+
+    // Turns a tile into floor. Returns `false` if it was already walkable.
+    fn carve(&mut self, p: Point) -> bool {
+        let tile = &mut self[p];
+        if tile.kind.is_walkable() {
+            false
+        } else {
+            tile.kind = TileKind::Floor;
+            true
+        }
+    }
+
+    // Returns a random cardinal step from `p`, without ever reaching the outer border.
+    fn random_step(&self, p: Point, rng: &mut dyn RngCore) -> Point {
+        let (dx, dy) = [(0, 1), (1, 0), (0, -1), (-1, 0)][rng.gen_range(0, 4)];
+
+        let x = (p.x() as i32 + dx).max(1).min(self.width as i32 - 2);
+        let y = (p.y() as i32 + dy).max(1).min(self.height as i32 - 2);
+
+        Point::new(x as u32, y as u32)
+    }
+
+    // Turns every walkable tile which cannot be reached from `start` into a wall.
+    fn remove_unreachable(&mut self, start: Point) {
+        let mut reachable = vec![false; self.tiles.len()];
+        for p in self.reachable_from(start) {
+            reachable[self.pt_to_idx(p)] = true;
+        }
+
+        for (tile, reachable) in self.tiles.iter_mut().zip(reachable) {
+            if !reachable {
+                tile.kind = TileKind::Wall;
+            }
+        }
+    }
+
+    // Places closed doors wherever a corridor enters a room through its walls.
+    //
+    // Only tiles squeezed between two walls get a door, so that rooms carved
+    // into each other or corridors running along a wall are left open.
+    fn place_doors(&mut self) {
+        let rooms = self.rooms.clone();
+
+        for room in &rooms {
+            let horizontal = (room.left() + 1..room.right())
+                .flat_map(|x| vec![Point::new(x, room.bottom()), Point::new(x, room.top())]);
+            let vertical = (room.bottom() + 1..room.top())
+                .flat_map(|y| vec![Point::new(room.left(), y), Point::new(room.right(), y)]);
+
+            for p in horizontal.chain(vertical) {
+                if self[p].kind == TileKind::Floor && self.is_chokepoint(p) {
+                    self[p].kind = TileKind::ClosedDoor;
+                }
+            }
+        }
+    }
+
+    // Returns whether the tile at `p` has walls on two opposite sides.
+    fn is_chokepoint(&self, p: Point) -> bool {
+        let is_wall = |dx, dy| self[p.translate(dx, dy)].kind == TileKind::Wall;
+
+        (is_wall(-1, 0) && is_wall(1, 0) && !is_wall(0, -1) && !is_wall(0, 1))
+            || (is_wall(0, -1) && is_wall(0, 1) && !is_wall(-1, 0) && !is_wall(1, 0))
+    }
+
+    fn xy_to_idx(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    fn pt_to_idx(&self, p: Point) -> usize {
+        (p.y() * self.width + p.x()) as usize
+    }
+
+    fn create_room(&mut self, room: &Rect) {
+        for y in room.bottom() + 1..room.top() {
+            for x in room.left() + 1..room.right() {
+                let idx = self.xy_to_idx(x, y);
+                self.tiles[idx].kind = TileKind::Floor
+            }
+        }
+    }
+
+    fn create_horizontal_corridor(&mut self, x1: u32, x2: u32, y: u32) {
+        for x in x1.min(x2)..=x1.max(x2) {
+            let idx = self.xy_to_idx(x, y);
+            self.tiles[idx].kind = TileKind::Floor;
+        }
+    }
+
+    fn create_vertical_corridor(&mut self, y1: u32, y2: u32, x: u32) {
+        for y in y1.min(y2)..=y1.max(y2) {
+            let idx = self.xy_to_idx(x, y);
+            self.tiles[idx].kind = TileKind::Floor;
+        }
+    }
+}
+
+impl Index<Point> for WorldMap {
+    type Output = TileState;
+
+    fn index(&self, index: Point) -> &Self::Output {
+        &self.tiles[self.pt_to_idx(index)]
+    }
+}
+
+impl Index<&Point> for WorldMap {
+    type Output = TileState;
+
+    fn index(&self, index: &Point) -> &Self::Output {
+        &self.tiles[self.pt_to_idx(*index)]
+    }
+}
+
+impl IndexMut<Point> for WorldMap {
+    fn index_mut(&mut self, index: Point) -> &mut Self::Output {
+        let idx = self.pt_to_idx(index);
+        &mut self.tiles[idx]
+    }
+}
+
+impl IndexMut<&Point> for WorldMap {
+    fn index_mut(&mut self, index: &Point) -> &mut Self::Output {
+        let idx = self.pt_to_idx(*index);
+        &mut self.tiles[idx]
+    }
+}
+
+/// Implementation of the FoV algorithm using recursive shadowcasting.
+///
+/// The algorithm itself is described in great detail at [RogueBasin].
+/// This is based on the C++ implementation of the algorithm available [here].
+///
+/// [RogueBasin]: http://roguebasin.roguelikedevelopment.org/index.php?title=FOV_using_recursive_shadowcasting
+/// [here]: http://roguebasin.roguelikedevelopment.org/index.php?title=C%2B%2B_shadowcasting_implementation
+pub struct ShadowcastFoV<'a> {
+    x: i32,
+    y: i32,
+    radius: i32,
+    map: &'a WorldMap,
+    visible: HashSet<Point>,
+}
+
+impl<'a> ShadowcastFoV<'a> {
+    const DIAGONALS_MULTIPLIES: [[i32; 8]; 4] = [
+        [1, 0, 0, -1, -1, 0, 0, 1],
+        [0, 1, -1, 0, 0, -1, 1, 0],
+        [0, 1, 1, 0, 0, -1, -1, 0],
+        [1, 0, 0, 1, -1, 0, 0, -1],
+    ];
+
+    /// Executes a run of the algorithm on the map for the specified circle.
+    pub fn run(map: &WorldMap, x: u32, y: u32, radius: u32) -> HashSet<Point> {
+        let mut fov = ShadowcastFoV {
+            map,
+            x: x as i32,
+            y: y as i32,
+            radius: radius as i32,
+            visible: HashSet::with_capacity((radius * radius * 4) as usize),
+        };
+
+        for i in 0..8 {
+            fov.cast_light(
+                1,
+                1.0,
+                0.0,
+                (
+                    ShadowcastFoV::DIAGONALS_MULTIPLIES[0][i],
+                    ShadowcastFoV::DIAGONALS_MULTIPLIES[1][i],
+                    ShadowcastFoV::DIAGONALS_MULTIPLIES[2][i],
+                    ShadowcastFoV::DIAGONALS_MULTIPLIES[3][i],
+                ),
+            );
+        }
+
+        fov.visible
+    }
+
+    fn cast_light(&mut self, row: i32, mut start: f32, end: f32, mul: (i32, i32, i32, i32)) {
+        let mut blocked = false;
+        let mut next_start_slope = start;
+
+        if start < end {
+            return;
+        }
+
+        for i in row..=self.radius {
+            if blocked {
+                break;
+            }
+            for dx in -i..=0 {
+                let dy = -i;
+                let l_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+                let r_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+
+                if start < r_slope {
+                    continue;
+                } else if end > l_slope {
+                    break;
+                }
+
+                let sax = dx * mul.0 + dy * mul.1;
+                let say = dx * mul.2 + dy * mul.3;
+                if (sax < 0 && sax.abs() > self.x) || (say < 0 && say.abs() > self.y) {
+                    continue;
+                }
+
+                let ax = self.x + sax;
+                let ay = self.y + say;
+                if ax >= self.map.width() as i32 || ay >= self.map.height() as i32 {
+                    continue;
+                }
+
+                let radius2 = self.radius * self.radius;
+                if (dx * dx + dy * dy) < radius2 {
+                    self.visible.insert(Point::new(ax as u32, ay as u32));
+                }
+
+                if blocked {
+                    if self
+                        .map
+                        .get((ax as u32, ay as u32).into())
+                        .map(|t| t.is_solid())
+                        .unwrap_or(true)
+                    {
+                        next_start_slope = r_slope;
+                        continue;
+                    } else {
+                        blocked = false;
+                        start = next_start_slope;
+                    }
+                } else if self
+                    .map
+                    .get((ax as u32, ay as u32).into())
+                    .map(|t| t.is_solid())
+                    .unwrap_or(true)
+                {
+                    blocked = true;
+                    self.cast_light(i + 1, start, l_slope, mul);
+                    next_start_slope = r_slope;
+                }
+            }
+        }
+    }
+}
+
+/// Computes a path between two points on the map, if it exists.
+///
+/// The resulting path contains the start and end points as first and last elements.
+/// Each step costs as much as walking onto the terrain it leads to.
+/// If `opens_doors` is set, the path can also go through closed doors.
+pub fn a_star_search(
+    map: &WorldMap,
+    start: Point,
+    end: Point,
+    opens_doors: bool,
+) -> Option<Vec<Point>> {
+    pathfinding::prelude::astar(
+        &start,
+        |&pt| {
+            // Workaround to allow pathfinding to end up on a blocked tile
+            if math::distance_2d(pt, end) == 1 {
+                return vec![(end, 1)];
+            }
+
+            let mut exits = map.get_adjacent_exits(pt);
+            if opens_doors {
+                exits.extend(map.get_adjacent_doors(pt));
+            }
+
+            exits
+                .into_iter()
+                .filter_map(|p| map[p].kind.walk_cost().map(|cost| (p, cost)))
+                .collect()
+        },
+        |&pt| math::distance_2d(pt, end),
+        |&pt| pt == end,
+    )
+    .map(|(path, _)| path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    // Generates a level from a fresh generator, in a form that can be compared.
+    fn generate(seed: u64, depth: u32) -> (String, Point, Vec<Vec<Point>>) {
+        let level = generate_level(80, 50, depth, &mut StdRng::seed_from_u64(seed));
+        let map = ron::ser::to_string(&level.map).unwrap();
+        (map, level.start, level.spawn_regions)
+    }
+
+    #[test]
+    fn same_seed_generates_same_level() {
+        for seed in 0..8 {
+            for depth in 1..=3 {
+                assert_eq!(generate(seed, depth), generate(seed, depth));
+            }
+        }
+    }
+
+    #[test]
+    fn every_level_has_a_way_down() {
+        for seed in 0..32 {
+            let level = generate_level(80, 50, 2, &mut StdRng::seed_from_u64(seed));
+            let exit = level.map.find(TileKind::DownStairs);
+
+            assert!(exit.is_some(), "no way down with seed {}", seed);
+            assert_ne!(exit, Some(level.start));
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use super::{Level, MapBuilder, WorldMap};
+use crate::math::Rect;
+
+use rand::{Rng, RngCore};
+
+/// Classic dungeon generator placing random non-overlapping rooms and joining them
+/// with L-shaped corridors.
+pub struct RoomsAndCorridors {
+    pub max_rooms: usize,
+    pub min_size: u32,
+    pub max_size: u32,
+}
+
+impl Default for RoomsAndCorridors {
+    fn default() -> Self {
+        RoomsAndCorridors {
+            max_rooms: 30,
+            min_size: 7,
+            max_size: 12,
+        }
+    }
+}
+
+impl MapBuilder for RoomsAndCorridors {
+    fn build(&self, width: u32, height: u32, rng: &mut dyn RngCore) -> Level {
+        let mut map = WorldMap::new(width, height);
+
+        for _ in 0..self.max_rooms {
+            let w = rng.gen_range(self.min_size, self.max_size);
+            let h = rng.gen_range(self.min_size, self.max_size);
+            let x = rng.gen_range(1, width - w - 1);
+            let y = rng.gen_range(1, height - h - 1);
+
+            let room = Rect::new(x, y, w, h);
+
+            if !map.rooms.iter().any(|other| room.intersects(other)) {
+                map.create_room(&room);
+
+                if let Some(rp) = map.rooms.last() {
+                    let (x1, y1) = (rp.center()[0], rp.center()[1]);
+                    let (x2, y2) = (room.center()[0], room.center()[1]);
+
+                    if rng.gen::<bool>() {
+                        map.create_horizontal_corridor(x1, x2, y1);
+                        map.create_vertical_corridor(y1, y2, x2);
+                    } else {
+                        map.create_vertical_corridor(y1, y2, x1);
+                        map.create_horizontal_corridor(x1, x2, y2);
+                    }
+                }
+
+                map.rooms.push(room);
+            }
+        }
+
+        map.place_doors();
+
+        // The player starts in the first room, while all the others get populated
+        Level {
+            start: map.rooms[0].center(),
+            spawn_regions: map.rooms[1..].iter().map(Rect::inner_points).collect(),
+            map,
+        }
+    }
+}
@@ -1,5 +1,8 @@
 //! This module contains the core of the game logic which does not fit into any ECS category.
-//! This include map structure, entity spawning logic etc.
+//! This include map structure, entity spawning logic, persistence etc.
 
 pub mod map;
+pub mod raws;
+pub mod save;
 pub mod spawn;
+pub mod spawn_table;
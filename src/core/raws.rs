@@ -0,0 +1,186 @@
+//! Data-driven definitions of monsters and items, also known as "raws".
+//!
+//! Templates are read from a RON file in the assets folder when a run starts,
+//! so that new content can be added without recompiling the game.
+//! The spawning logic in [`spawn`] turns them into actual entities.
+//!
+//! [`spawn`]: super::spawn
+
+use crate::{
+    components::{EquipmentSlot, StatusEffect, NORMAL_SPEED},
+    core::spawn_table::SpawnTable,
+};
+
+use amethyst::utils::application_root_dir;
+use serde::Deserialize;
+use std::fs;
+
+/// Path of the raws file, relative to the application root.
+const RAWS_FILE: &str = "assets/raws/spawns.ron";
+
+/// Resource holding all the monster and item templates.
+#[derive(Default, Deserialize)]
+pub struct Raws {
+    pub monsters: Vec<MonsterRaw>,
+    pub items: Vec<ItemRaw>,
+    pub monsters_per_region: CountRaw,
+    pub items_per_region: CountRaw,
+}
+
+impl Raws {
+    /// Reads the templates from the raws file.
+    pub fn load() -> amethyst::Result<Raws> {
+        let path = application_root_dir()?.join(RAWS_FILE);
+        let raws = ron::de::from_str(&fs::read_to_string(path)?)?;
+        Ok(raws)
+    }
+
+    /// Returns the monster template with the given name, if any.
+    pub fn monster(&self, name: &str) -> Option<&MonsterRaw> {
+        self.monsters.iter().find(|m| m.name == name)
+    }
+
+    /// Returns the item template with the given name, if any.
+    pub fn item(&self, name: &str) -> Option<&ItemRaw> {
+        self.items.iter().find(|i| i.name == name)
+    }
+
+    /// Builds the table of the monsters that can spawn at the given depth.
+    pub fn monster_table(&self, depth: u32) -> SpawnTable {
+        self.monsters.iter().fold(SpawnTable::new(), |table, m| {
+            table.add(&m.name, m.spawn.weight_at(depth))
+        })
+    }
+
+    /// Builds the table of the items that can spawn at the given depth.
+    pub fn item_table(&self, depth: u32) -> SpawnTable {
+        self.items.iter().fold(SpawnTable::new(), |table, i| {
+            table.add(&i.name, i.spawn.weight_at(depth))
+        })
+    }
+}
+
+/// How many entities of a kind a spawn region can hold at most.
+///
+/// The maximum is `max` on the first level, and grows by `depth_max` at each level below it.
+#[derive(Default, Clone, Deserialize)]
+pub struct CountRaw {
+    pub max: u32,
+    #[serde(default)]
+    pub depth_max: u32,
+}
+
+impl CountRaw {
+    /// Returns the maximum number of entities in a region at the given depth.
+    pub fn max_at(&self, depth: u32) -> u32 {
+        self.max + self.depth_max * depth.saturating_sub(1)
+    }
+}
+
+/// Where and how often an entity spawns in the dungeon.
+///
+/// The weight is the one at `min_depth`, and changes by `depth_weight` at each level
+/// below it, so that entities can become more common or rarer as the player goes deeper.
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct SpawnRaw {
+    pub weight: u32,
+    pub depth_weight: i32,
+    pub min_depth: u32,
+    pub max_depth: Option<u32>,
+}
+
+impl SpawnRaw {
+    /// Returns the weight of the entity at the given depth, or zero if it cannot spawn there.
+    pub fn weight_at(&self, depth: u32) -> u32 {
+        if depth < self.min_depth || self.max_depth.map(|max| depth > max).unwrap_or(false) {
+            return 0;
+        }
+
+        let levels = (depth - self.min_depth) as i32;
+        i32::max(0, self.weight as i32 + self.depth_weight * levels) as u32
+    }
+}
+
+impl Default for SpawnRaw {
+    fn default() -> Self {
+        SpawnRaw {
+            weight: 1,
+            depth_weight: 0,
+            min_depth: 1,
+            max_depth: None,
+        }
+    }
+}
+
+/// How an entity looks on the map.
+#[derive(Clone, Deserialize)]
+pub struct RenderRaw {
+    pub glyph: char,
+    pub tint: (f32, f32, f32, f32),
+}
+
+/// Combat stats of a monster, before scaling them with depth.
+#[derive(Clone, Deserialize)]
+pub struct StatsRaw {
+    pub hp: i32,
+    pub defense: i32,
+    pub power: i32,
+}
+
+/// Template for a monster.
+#[derive(Clone, Deserialize)]
+pub struct MonsterRaw {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub render: RenderRaw,
+    pub faction: u32,
+    pub vision: u32,
+    #[serde(default)]
+    pub opens_doors: bool,
+    #[serde(default = "normal_speed")]
+    pub speed: u32,
+    /// Chance, in percent, of the monster being found asleep.
+    #[serde(default)]
+    pub asleep: u32,
+    /// Percentage of its max HP below which the monster runs away, if it ever does.
+    #[serde(default)]
+    pub flees_below: Option<i32>,
+    pub stats: StatsRaw,
+    pub experience: u32,
+    #[serde(default)]
+    pub spawn: SpawnRaw,
+}
+
+// Speed of the monsters which do not specify any.
+fn normal_speed() -> u32 {
+    NORMAL_SPEED
+}
+
+/// Template for an item. Every effect is optional, and an item can have many.
+#[derive(Clone, Deserialize)]
+pub struct ItemRaw {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub render: RenderRaw,
+    #[serde(default)]
+    pub consumable: bool,
+    #[serde(default)]
+    pub range: Option<u32>,
+    #[serde(default)]
+    pub heals: Option<i32>,
+    #[serde(default)]
+    pub damage: Option<i32>,
+    #[serde(default)]
+    pub effect: Option<(StatusEffect, u32)>,
+    #[serde(default)]
+    pub slot: Option<EquipmentSlot>,
+    #[serde(default)]
+    pub power_bonus: Option<i32>,
+    #[serde(default)]
+    pub defense_bonus: Option<i32>,
+    #[serde(default)]
+    pub spawn: SpawnRaw,
+}
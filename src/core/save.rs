@@ -0,0 +1,452 @@
+//! Persistence of the running game, both to disk and across dungeon levels.
+//!
+//! The whole game state is converted into a [`SaveGame`], which is then serialized as RON.
+//! Entities are stored as flat lists of components, and references between entities
+//! (such as the owner of an item in a backpack) are stored as indexes into that list.
+//!
+//! The same representation is used to keep the levels left behind by the player
+//! in the [`LevelCache`], so that they can be restored when the player comes back.
+//!
+//! The player's [`Settings`] are kept in a separate file, since they are not tied to any run.
+
+use crate::{
+    components::*,
+    core::map::WorldMap,
+    math::Point,
+    resources::{CombatLog, Depth, RngState, RunStats, SeededRng, Settings},
+    systems::GameClock,
+};
+
+use amethyst::{
+    assets::Handle,
+    core::Hidden,
+    ecs::{Entity, Join},
+    prelude::*,
+    renderer::{palette::Srgba, resources::Tint, SpriteRender, SpriteSheet},
+    utils::application_root_dir,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Version of the save file format. Bump this whenever the format changes.
+const SAVE_VERSION: u32 = 12;
+
+/// Name of the save file, relative to the application root.
+const SAVE_FILE: &str = "savegame.ron";
+
+/// Name of the settings file, relative to the application root.
+const SETTINGS_FILE: &str = "settings.ron";
+
+/// Snapshot of the whole game state.
+#[derive(Serialize, Deserialize)]
+struct SaveGame {
+    version: u32,
+    seed: u64,
+    rng: RngState,
+    depth: Depth,
+    map: WorldMap,
+    log: CombatLog,
+    clock: GameClock,
+    stats: RunStats,
+    levels: LevelCache,
+    entities: Vec<EntityData>,
+}
+
+/// Serializable representation of a single entity and all its gameplay components.
+#[derive(Clone, Serialize, Deserialize)]
+struct EntityData {
+    player: bool,
+    pickable: bool,
+    consumable: bool,
+    blocks_tile: bool,
+    opens_doors: bool,
+    acts_on_turns: Option<ActsOnTurns>,
+    brain: Option<Brain>,
+    faction: Option<Faction>,
+    name: Option<Name>,
+    description: Option<Description>,
+    position: Option<Position>,
+    ranged: Option<Ranged>,
+    viewshed: Option<u32>,
+    heals_user: Option<HealsUser>,
+    inflicts_damage: Option<InflictsDamage>,
+    applies_effect: Option<AppliesEffect>,
+    status_effects: Option<StatusEffects>,
+    combat_stats: Option<CombatStats>,
+    experience: Option<Experience>,
+    awards_experience: Option<AwardsExperience>,
+    equippable: Option<Equippable>,
+    melee_power_bonus: Option<MeleePowerBonus>,
+    defense_bonus: Option<DefenseBonus>,
+    in_backpack: Option<usize>,
+    equipped: Option<(usize, EquipmentSlot)>,
+    sprite: Option<(usize, (f32, f32, f32, f32))>,
+}
+
+/// A dungeon level which is not being played, stored outside of the world.
+#[derive(Clone, Serialize, Deserialize)]
+struct StoredLevel {
+    map: WorldMap,
+    entities: Vec<EntityData>,
+}
+
+/// Resource holding all the visited levels but the current one, indexed by depth.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct LevelCache(HashMap<u32, StoredLevel>);
+
+/// Returns the path of the save file.
+pub fn save_path() -> amethyst::Result<PathBuf> {
+    Ok(application_root_dir()?.join(SAVE_FILE))
+}
+
+/// Deletes the save file, if present.
+pub fn delete_save() -> amethyst::Result<()> {
+    let path = save_path()?;
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Reads the settings file, falling back to the default settings if it is missing or invalid.
+pub fn load_settings() -> Settings {
+    application_root_dir()
+        .ok()
+        .and_then(|root| fs::read_to_string(root.join(SETTINGS_FILE)).ok())
+        .and_then(|ron| ron::de::from_str(&ron).ok())
+        .unwrap_or_default()
+}
+
+/// Writes the given settings to the settings file.
+pub fn save_settings(settings: &Settings) -> amethyst::Result<()> {
+    let ron = ron::ser::to_string_pretty(settings, Default::default())?;
+    fs::write(application_root_dir()?.join(SETTINGS_FILE), ron)?;
+
+    Ok(())
+}
+
+/// Writes the current game state to the given path.
+pub fn save_game(world: &World, path: &Path) -> amethyst::Result<()> {
+    // Only entities placed in the world or carried by someone are part of the game state.
+    // Anything else (eg. the camera and the console) is recreated when the game is loaded.
+    let saved = {
+        let entities = world.entities();
+        let positions = world.read_storage::<Position>();
+        let backpacks = world.read_storage::<InBackpack>();
+
+        (&entities)
+            .join()
+            .filter(|e| positions.contains(*e) || backpacks.contains(*e))
+            .collect::<Vec<_>>()
+    };
+
+    let save = SaveGame {
+        version: SAVE_VERSION,
+        seed: world.read_resource::<SeededRng>().seed(),
+        rng: world.read_resource::<SeededRng>().state(),
+        depth: *world.read_resource::<Depth>(),
+        map: world.read_resource::<WorldMap>().clone(),
+        log: world.read_resource::<CombatLog>().clone(),
+        clock: *world.read_resource::<GameClock>(),
+        stats: world.read_resource::<RunStats>().clone(),
+        levels: world.read_resource::<LevelCache>().clone(),
+        entities: store_entities(world, &saved),
+    };
+
+    let ron = ron::ser::to_string_pretty(&save, Default::default())?;
+    fs::write(path, ron)?;
+
+    Ok(())
+}
+
+/// Restores the game state stored at the given path. Returns the player entity.
+///
+/// The world is expected not to contain any game entity yet.
+pub fn load_game(
+    world: &mut World,
+    path: &Path,
+    sheet: Handle<SpriteSheet>,
+) -> amethyst::Result<Entity> {
+    let save: SaveGame = ron::de::from_str(&fs::read_to_string(path)?)?;
+
+    if save.version != SAVE_VERSION {
+        return Err(amethyst::Error::from_string(format!(
+            "unsupported save version {} (expected {})",
+            save.version, SAVE_VERSION
+        )));
+    }
+
+    // The generator picks up right where it was, just like in a run that was never reloaded
+    world.insert(SeededRng::resume(save.seed, save.rng));
+    world.insert(save.depth);
+    world.insert(save.map);
+    world.insert(save.log);
+    world.insert(save.clock);
+    world.insert(save.stats);
+    world.insert(save.levels);
+
+    let created = restore_entities(world, &save.entities, sheet);
+    let player = save
+        .entities
+        .iter()
+        .zip(created)
+        .find(|(data, _)| data.player)
+        .map(|(_, e)| e)
+        .ok_or_else(|| amethyst::Error::from_string("no player in save file"))?;
+
+    // Insert player position as resource
+    let pos = world
+        .read_storage::<Position>()
+        .get(player)
+        .map(|&Position(p)| p)
+        .unwrap_or_default();
+    world.insert::<Point>(pos);
+
+    Ok(player)
+}
+
+/// Moves the current level out of the world and into the [`LevelCache`].
+///
+/// The level-bound entities are all the ones placed on the map, except for the player,
+/// and the items carried by anyone but the player.
+pub fn stash_level(world: &mut World, depth: u32) {
+    let bound = {
+        let entities = world.entities();
+        let players = world.read_storage::<Player>();
+        let positions = world.read_storage::<Position>();
+        let backpacks = world.read_storage::<InBackpack>();
+
+        (&entities)
+            .join()
+            .filter(|&e| {
+                let carried_by_player = backpacks
+                    .get(e)
+                    .map(|InBackpack { owner }| players.contains(*owner))
+                    .unwrap_or(false);
+
+                (positions.contains(e) || backpacks.contains(e))
+                    && !players.contains(e)
+                    && !carried_by_player
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let level = StoredLevel {
+        map: world.read_resource::<WorldMap>().clone(),
+        entities: store_entities(world, &bound),
+    };
+    world.write_resource::<LevelCache>().0.insert(depth, level);
+
+    world
+        .delete_entities(&bound)
+        .expect("deleting level entities failed");
+}
+
+/// Moves a previously visited level from the [`LevelCache`] back into the world.
+///
+/// Returns `false` if the level at the given depth was never visited.
+pub fn restore_level(world: &mut World, depth: u32, sheet: Handle<SpriteSheet>) -> bool {
+    let level = world.write_resource::<LevelCache>().0.remove(&depth);
+
+    match level {
+        Some(StoredLevel { map, entities }) => {
+            world.insert(map);
+            restore_entities(world, &entities, sheet);
+            true
+        }
+        None => false,
+    }
+}
+
+// Converts the given entities into their serializable representation.
+// References to entities outside of the list are dropped.
+fn store_entities(world: &World, stored: &[Entity]) -> Vec<EntityData> {
+    let indexes = stored
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (*e, i))
+        .collect::<HashMap<_, _>>();
+
+    let backpacks = world.read_storage::<InBackpack>();
+    let equipped = world.read_storage::<Equipped>();
+    let sprites = world.read_storage::<SpriteRender>();
+    let tints = world.read_storage::<Tint>();
+
+    stored
+        .iter()
+        .map(|&e| EntityData {
+            player: world.read_storage::<Player>().contains(e),
+            pickable: world.read_storage::<Pickable>().contains(e),
+            consumable: world.read_storage::<Consumable>().contains(e),
+            blocks_tile: world.read_storage::<BlocksTile>().contains(e),
+            opens_doors: world.read_storage::<OpensDoors>().contains(e),
+            acts_on_turns: world.read_storage::<ActsOnTurns>().get(e).copied(),
+            brain: world.read_storage::<Brain>().get(e).cloned(),
+            faction: world.read_storage::<Faction>().get(e).cloned(),
+            name: world.read_storage::<Name>().get(e).cloned(),
+            description: world.read_storage::<Description>().get(e).cloned(),
+            position: world.read_storage::<Position>().get(e).cloned(),
+            ranged: world.read_storage::<Ranged>().get(e).cloned(),
+            viewshed: world.read_storage::<Viewshed>().get(e).map(|vs| vs.range),
+            heals_user: world.read_storage::<HealsUser>().get(e).cloned(),
+            inflicts_damage: world.read_storage::<InflictsDamage>().get(e).cloned(),
+            applies_effect: world.read_storage::<AppliesEffect>().get(e).cloned(),
+            status_effects: world.read_storage::<StatusEffects>().get(e).cloned(),
+            combat_stats: world.read_storage::<CombatStats>().get(e).cloned(),
+            experience: world.read_storage::<Experience>().get(e).cloned(),
+            awards_experience: world.read_storage::<AwardsExperience>().get(e).cloned(),
+            equippable: world.read_storage::<Equippable>().get(e).cloned(),
+            melee_power_bonus: world.read_storage::<MeleePowerBonus>().get(e).cloned(),
+            defense_bonus: world.read_storage::<DefenseBonus>().get(e).cloned(),
+            in_backpack: backpacks
+                .get(e)
+                .and_then(|InBackpack { owner }| indexes.get(owner).copied()),
+            equipped: equipped.get(e).and_then(|Equipped { owner, slot }| {
+                indexes.get(owner).map(|&owner| (owner, *slot))
+            }),
+            sprite: sprites.get(e).map(|sprite| {
+                let tint = tints
+                    .get(e)
+                    .map(|Tint(tint)| *tint)
+                    .unwrap_or_else(|| Srgba::new(1., 1., 1., 1.));
+                (
+                    sprite.sprite_number,
+                    (tint.red, tint.green, tint.blue, tint.alpha),
+                )
+            }),
+        })
+        .collect()
+}
+
+// Creates entities out of their serializable representation, in the same order.
+fn restore_entities(
+    world: &mut World,
+    stored: &[EntityData],
+    sheet: Handle<SpriteSheet>,
+) -> Vec<Entity> {
+    // First pass: create all the entities, so that references between them can be resolved
+    let created = stored
+        .iter()
+        .map(|data| {
+            let mut builder = world.create_entity();
+
+            if data.player {
+                builder = builder.with(Player);
+            }
+            if data.pickable {
+                builder = builder.with(Pickable);
+            }
+            if data.consumable {
+                builder = builder.with(Consumable);
+            }
+            if data.blocks_tile {
+                builder = builder.with(BlocksTile);
+            }
+            if data.opens_doors {
+                builder = builder.with(OpensDoors);
+            }
+            if let Some(c) = data.acts_on_turns {
+                builder = builder.with(c);
+            }
+            if let Some(c) = data.brain.clone() {
+                builder = builder.with(c);
+            }
+            if let Some(c) = data.faction.clone() {
+                builder = builder.with(c);
+            }
+            if let Some(c) = data.name.clone() {
+                builder = builder.with(c);
+            }
+            if let Some(c) = data.description.clone() {
+                builder = builder.with(c);
+            }
+            if let Some(c) = data.position.clone() {
+                builder = builder.with(c);
+            }
+            if let Some(c) = data.ranged.clone() {
+                builder = builder.with(c);
+            }
+            if let Some(range) = data.viewshed {
+                builder = builder.with(Viewshed::new(range));
+            }
+            if let Some(c) = data.heals_user.clone() {
+                builder = builder.with(c);
+            }
+            if let Some(c) = data.inflicts_damage.clone() {
+                builder = builder.with(c);
+            }
+            if let Some(c) = data.applies_effect.clone() {
+                builder = builder.with(c);
+            }
+            if let Some(c) = data.status_effects.clone() {
+                builder = builder.with(c);
+            }
+            if let Some(c) = data.combat_stats.clone() {
+                builder = builder.with(c);
+            }
+            if let Some(c) = data.experience.clone() {
+                builder = builder.with(c);
+            }
+            if let Some(c) = data.awards_experience.clone() {
+                builder = builder.with(c);
+            }
+            if let Some(c) = data.equippable.clone() {
+                builder = builder.with(c);
+            }
+            if let Some(c) = data.melee_power_bonus.clone() {
+                builder = builder.with(c);
+            }
+            if let Some(c) = data.defense_bonus.clone() {
+                builder = builder.with(c);
+            }
+            if let Some((glyph, (r, g, b, a))) = data.sprite {
+                builder = builder
+                    .with(SpriteRender {
+                        sprite_sheet: sheet.clone(),
+                        sprite_number: glyph,
+                    })
+                    .with(Tint(Srgba::new(r, g, b, a)));
+
+                // Visibility of non-player entities is recomputed on the next turn
+                if !data.player {
+                    builder = builder.with(Hidden);
+                }
+            }
+
+            builder.build()
+        })
+        .collect::<Vec<_>>();
+
+    // Second pass: restore the references between entities
+    for (data, &e) in stored.iter().zip(created.iter()) {
+        if let Some(owner) = data.in_backpack {
+            world
+                .write_storage()
+                .insert(
+                    e,
+                    InBackpack {
+                        owner: created[owner],
+                    },
+                )
+                .unwrap();
+        }
+        if let Some((owner, slot)) = data.equipped {
+            world
+                .write_storage()
+                .insert(
+                    e,
+                    Equipped {
+                        owner: created[owner],
+                        slot,
+                    },
+                )
+                .unwrap();
+        }
+    }
+
+    created
+}
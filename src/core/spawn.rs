@@ -1,18 +1,19 @@
-use crate::{components::*, math::Point, utils};
+use crate::{
+    components::*,
+    core::raws::{Raws, RenderRaw},
+    math::Point,
+    resources::SeededRng,
+    utils,
+};
 
 use amethyst::{
     assets::Handle,
     core::Hidden,
-    ecs::Entity,
+    ecs::{Entity, EntityBuilder},
     prelude::*,
     renderer::{palette::Srgba, resources::Tint, SpriteRender, SpriteSheet},
 };
-
-/// Maximum number of monsters that can spawn in a room.
-pub const MAX_MONSTERS: usize = 4;
-
-/// Maximum number of items that can spawn in a room.
-pub const MAX_ITEMS: usize = 2;
+use rand::Rng;
 
 /// Spawns the player entity at the given coordinates.
 pub fn player(world: &mut World, pos: Point, sheet: Handle<SpriteSheet>) -> Entity {
@@ -26,6 +27,8 @@ pub fn player(world: &mut World, pos: Point, sheet: Handle<SpriteSheet>) -> Enti
         .with(ActsOnTurns::default())
         .with(Position(pos))
         .with(BlocksTile)
+        .with(OpensDoors)
+        .with(Experience::default())
         .with(Viewshed::new(25))
         .with(CombatStats {
             max_hp: 30,
@@ -38,100 +41,156 @@ pub fn player(world: &mut World, pos: Point, sheet: Handle<SpriteSheet>) -> Enti
             sprite_number: utils::to_glyph('@'),
         })
         .with(Name("Hero".to_string()))
+        .with(Description(String::from(
+            "A brave adventurer, looking for glory in the depths.",
+        )))
         .with(Tint(Srgba::new(0.7, 0.5, 0.0, 1.0)))
         .build()
 }
 
-/// Spawns a random monster at the given coordinates.
-pub fn random_monster(world: &mut World, pos: Point, sheet: Handle<SpriteSheet>) -> Entity {
-    if rand::random() {
-        orc(world, pos, sheet)
-    } else {
-        goblin(world, pos, sheet)
-    }
-}
+/// Spawns a random monster fit for the given depth at the given coordinates.
+///
+/// Monsters are drawn from the spawn table for that depth.
+pub fn random_monster(
+    world: &mut World,
+    pos: Point,
+    depth: u32,
+    sheet: Handle<SpriteSheet>,
+) -> Option<Entity> {
+    let table = world.read_resource::<Raws>().monster_table(depth);
+    let name = table
+        .roll(&mut *world.write_resource::<SeededRng>())
+        .map(String::from);
 
-/// Spawns a random item at the given coordinates.
-pub fn random_item(world: &mut World, pos: Point, sheet: Handle<SpriteSheet>) -> Entity {
-    if rand::random() {
-        health_potion(world, pos, sheet)
-    } else {
-        magic_missile_scroll(world, pos, sheet)
-    }
+    name.and_then(|name| monster(world, &name, pos, depth, sheet))
 }
 
-/// Spawns an orc at the given coordinates.
-pub fn orc(world: &mut World, pos: Point, sheet: Handle<SpriteSheet>) -> Entity {
-    monster(world, pos, utils::to_glyph('o'), "Orc", sheet)
-}
+/// Spawns a random item fit for the given depth at the given coordinates.
+///
+/// Items are drawn from the spawn table for that depth.
+pub fn random_item(
+    world: &mut World,
+    pos: Point,
+    depth: u32,
+    sheet: Handle<SpriteSheet>,
+) -> Option<Entity> {
+    let table = world.read_resource::<Raws>().item_table(depth);
+    let name = table
+        .roll(&mut *world.write_resource::<SeededRng>())
+        .map(String::from);
 
-/// Spawns a goblin at the given coordinates.
-pub fn goblin(world: &mut World, pos: Point, sheet: Handle<SpriteSheet>) -> Entity {
-    monster(world, pos, utils::to_glyph('g'), "Goblin", sheet)
+    name.and_then(|name| item(world, &name, pos, sheet))
 }
 
-// Spawns a monster at the given coordinates using the specified glyph and name.
-fn monster<S: ToString>(
+/// Spawns the monster with the given template name at the given coordinates.
+/// Monsters get tougher the deeper they are found.
+///
+/// Returns `None` if there is no monster template with such name.
+pub fn monster(
     world: &mut World,
+    name: &str,
     pos: Point,
-    glyph: usize,
-    name: S,
+    depth: u32,
     sheet: Handle<SpriteSheet>,
-) -> Entity {
-    world
-        .create_entity()
-        .with(Faction(1))
-        .with(ActsOnTurns::default())
+) -> Option<Entity> {
+    let raw = world.read_resource::<Raws>().monster(name)?.clone();
+    let bonus = depth as i32 - 1;
+    let state = if world.write_resource::<SeededRng>().gen_range(0, 100) < raw.asleep {
+        AiState::Sleeping
+    } else {
+        AiState::Wandering(None)
+    };
+
+    let mut builder = world.create_entity();
+    if raw.opens_doors {
+        builder = builder.with(OpensDoors);
+    }
+    if let Some(description) = raw.description {
+        builder = builder.with(Description(description));
+    }
+
+    let builder = builder
+        .with(Faction(raw.faction))
+        .with(ActsOnTurns::new(raw.speed))
+        .with(Brain::new(state, raw.flees_below))
         .with(Position(pos))
         .with(BlocksTile)
-        .with(Viewshed::new(8))
+        .with(Viewshed::new(raw.vision))
         .with(CombatStats {
-            max_hp: 16,
-            hp: 16,
-            defense: 1,
-            power: 4,
+            max_hp: raw.stats.hp + 2 * bonus,
+            hp: raw.stats.hp + 2 * bonus,
+            defense: raw.stats.defense + bonus / 3,
+            power: raw.stats.power + bonus / 2,
         })
-        .with(SpriteRender {
-            sprite_sheet: sheet,
-            sprite_number: glyph,
+        .with(AwardsExperience {
+            amount: raw.experience + 5 * bonus as u32,
         })
-        .with(Name(name.to_string()))
-        .with(Tint(Srgba::new(1.0, 0.0, 0.0, 1.0)))
-        .with(Hidden) // initially monsters are not visible
-        .build()
+        .with(Name(raw.name))
+        .with(Hidden); // initially monsters are not visible
+
+    Some(render(builder, &raw.render, sheet).build())
 }
 
-/// Spawns a health potion at the given coordinates.
-pub fn health_potion(world: &mut World, pos: Point, sheet: Handle<SpriteSheet>) -> Entity {
-    world
+/// Spawns the item with the given template name at the given coordinates.
+///
+/// Returns `None` if there is no item template with such name.
+pub fn item(
+    world: &mut World,
+    name: &str,
+    pos: Point,
+    sheet: Handle<SpriteSheet>,
+) -> Option<Entity> {
+    let raw = world.read_resource::<Raws>().item(name)?.clone();
+
+    let mut builder = world
         .create_entity()
         .with(Pickable)
-        .with(Consumable)
-        .with(HealsUser { amount: 8 })
         .with(Position(pos))
-        .with(SpriteRender {
-            sprite_sheet: sheet,
-            sprite_number: utils::to_glyph('¡'),
-        })
-        .with(Name(String::from("Health Potion")))
-        .with(Tint(Srgba::new(1.0, 0.0, 1.0, 1.0)))
-        .build()
+        .with(Name(raw.name));
+
+    if let Some(description) = raw.description {
+        builder = builder.with(Description(description));
+    }
+    if raw.consumable {
+        builder = builder.with(Consumable);
+    }
+    if let Some(range) = raw.range {
+        builder = builder.with(Ranged { range });
+    }
+    if let Some(amount) = raw.heals {
+        builder = builder.with(HealsUser { amount });
+    }
+    if let Some(amount) = raw.damage {
+        builder = builder.with(InflictsDamage { amount });
+    }
+    if let Some((effect, turns)) = raw.effect {
+        builder = builder.with(AppliesEffect { effect, turns });
+    }
+    if let Some(slot) = raw.slot {
+        builder = builder.with(Equippable { slot });
+    }
+    if let Some(amount) = raw.power_bonus {
+        builder = builder.with(MeleePowerBonus { amount });
+    }
+    if let Some(amount) = raw.defense_bonus {
+        builder = builder.with(DefenseBonus { amount });
+    }
+
+    Some(render(builder, &raw.render, sheet).build())
 }
 
-/// Spawns a scroll of magic missile, a ranged consumable scroll.
-pub fn magic_missile_scroll(world: &mut World, pos: Point, sheet: Handle<SpriteSheet>) -> Entity {
-    world
-        .create_entity()
-        .with(Pickable)
-        .with(Consumable)
-        .with(Ranged { range: 6 })
-        .with(InflictsDamage { amount: 8 })
-        .with(Position(pos))
+// Adds the sprite described by a template to an entity being built.
+fn render<'a>(
+    builder: EntityBuilder<'a>,
+    raw: &RenderRaw,
+    sheet: Handle<SpriteSheet>,
+) -> EntityBuilder<'a> {
+    let (r, g, b, a) = raw.tint;
+
+    builder
         .with(SpriteRender {
             sprite_sheet: sheet,
-            sprite_number: utils::to_glyph(')'),
+            sprite_number: utils::to_glyph(raw.glyph),
         })
-        .with(Name(String::from("Magic Missile Scroll")))
-        .with(Tint(Srgba::new(1.0, 0.75, 0.25, 1.0)))
-        .build()
+        .with(Tint(Srgba::new(r, g, b, a)))
 }
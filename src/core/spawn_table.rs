@@ -0,0 +1,106 @@
+//! Weighted random tables, used to decide what spawns in the dungeon.
+
+use rand::{Rng, RngCore};
+
+/// A list of names, each with a weight proportional to its chance of being picked.
+///
+/// Tables are independent of the game world: they are usually built from the raws
+/// for a given depth, but can be built and rolled on their own as well.
+#[derive(Default, Debug, Clone)]
+pub struct SpawnTable {
+    entries: Vec<(String, u32)>,
+    total: u32,
+}
+
+impl SpawnTable {
+    /// Creates an empty table.
+    pub fn new() -> SpawnTable {
+        SpawnTable::default()
+    }
+
+    /// Adds an entry to the table. Entries without any weight are left out.
+    pub fn add<S: ToString>(mut self, name: S, weight: u32) -> SpawnTable {
+        if weight > 0 {
+            self.entries.push((name.to_string(), weight));
+            self.total += weight;
+        }
+        self
+    }
+
+    /// Picks a random entry, according to the weights. Returns `None` if the table is empty.
+    pub fn roll(&self, rng: &mut dyn RngCore) -> Option<&str> {
+        if self.total == 0 {
+            return None;
+        }
+
+        let mut roll = rng.gen_range(0, self.total);
+        for (name, weight) in &self.entries {
+            if roll < *weight {
+                return Some(name);
+            }
+            roll -= weight;
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::raws::SpawnRaw;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn entries_without_weight_are_dropped() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let table = SpawnTable::new().add("Orc", 0).add("Goblin", 1);
+
+        for _ in 0..100 {
+            assert_eq!(table.roll(&mut rng), Some("Goblin"));
+        }
+        assert_eq!(SpawnTable::new().add("Orc", 0).roll(&mut rng), None);
+    }
+
+    #[test]
+    fn empty_table_rolls_nothing() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(SpawnTable::new().roll(&mut rng), None);
+    }
+
+    #[test]
+    fn rolls_follow_the_weights() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let table = SpawnTable::new().add("Orc", 3).add("Goblin", 1);
+
+        let orcs = (0..4000)
+            .filter(|_| table.roll(&mut rng) == Some("Orc"))
+            .count();
+        assert!(orcs > 2800 && orcs < 3200, "{} orcs out of 4000", orcs);
+    }
+
+    #[test]
+    fn weight_depends_on_depth() {
+        let spawn = SpawnRaw {
+            weight: 5,
+            depth_weight: -2,
+            min_depth: 2,
+            max_depth: Some(6),
+        };
+
+        assert_eq!(spawn.weight_at(1), 0);
+        assert_eq!(spawn.weight_at(2), 5);
+        assert_eq!(spawn.weight_at(3), 3);
+        assert_eq!(spawn.weight_at(4), 1);
+        assert_eq!(spawn.weight_at(5), 0);
+        assert_eq!(spawn.weight_at(7), 0);
+
+        let growing = SpawnRaw {
+            weight: 1,
+            depth_weight: 1,
+            ..SpawnRaw::default()
+        };
+        assert_eq!(growing.weight_at(1), 1);
+        assert_eq!(growing.weight_at(10), 10);
+    }
+}
@@ -63,6 +63,9 @@ pub trait Console {
 
     /// Puts a single colored glyph in the given cell.
     fn put<P: Into<Point>>(&mut self, pt: P, glyph: char, fg: Srgba);
+
+    /// Changes the color of the given cell, leaving its glyph untouched.
+    fn tint<P: Into<Point>>(&mut self, pt: P, fg: Srgba);
 }
 
 impl Console for ConsoleTileMap {
@@ -121,7 +124,7 @@ impl Console for ConsoleTileMap {
     ) {
         let pt = pt.into();
         let ratio = current as f32 / max as f32;
-        let filled = (ratio * width as f32).round() as u32;
+        let filled = u32::min(width, (ratio * width as f32).round() as u32);
 
         if filled > 0 {
             self.fill_region(Rect::new(pt.x(), pt.y(), filled, 1), '░', fill);
@@ -157,4 +160,12 @@ impl Console for ConsoleTileMap {
             tile.tint = fg;
         }
     }
+
+    fn tint<P: Into<Point>>(&mut self, pt: P, fg: Srgba) {
+        let pt = pt.into();
+
+        if let Some(tile) = self.get_mut(&Point3::new(pt.x(), pt.y(), 0)) {
+            tile.tint = fg;
+        }
+    }
 }
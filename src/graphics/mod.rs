@@ -2,15 +2,21 @@ pub mod console;
 pub mod renderer;
 
 use crate::{
-    components::{CombatStats, Player},
-    graphics::{console::Console, renderer::ConsoleTileMap},
-    resources::CombatLog,
+    components::{CombatStats, Experience, Name, Player, Position, StatusEffects},
+    core::map::WorldMap,
+    graphics::{
+        console::Console,
+        renderer::{self, ConsoleTileMap},
+    },
+    math::Point,
+    resources::{CombatLog, Depth, Settings},
 };
 
 use amethyst::{
     ecs::{Entity, Join},
     prelude::*,
     renderer::palette::Srgba,
+    tiles::Map,
     utils::fps_counter::FpsCounter,
 };
 
@@ -32,6 +38,7 @@ impl Ui {
     pub fn refresh(&mut self, world: &mut World) {
         self.update_infobox(world);
         self.update_fps_counter(world);
+        self.update_tooltip(world);
     }
 
     // Updates the infobox to reflect the current game state.
@@ -40,11 +47,87 @@ impl Ui {
             con.draw_box((0, 43, 80, 7));
         }
         self.update_combat_log(world);
+        self.update_depth_display(world);
         self.update_hp_display(world);
+        self.update_xp_display(world);
+        self.update_effects_display(world);
+    }
+
+    // Update the current depth in the infobox.
+    fn update_depth_display(&mut self, world: &mut World) {
+        if let Some(con) = world.write_storage::<CTM>().get_mut(self.console) {
+            con.print_color(
+                (1, 43),
+                format!(" Depth {} ", world.read_resource::<Depth>().0),
+                Srgba::new(1., 1., 0., 1.),
+            );
+        }
+    }
+
+    // Draws a tooltip next to the mouse cursor, listing the entities in view under it.
+    fn update_tooltip(&mut self, world: &mut World) {
+        let pt = match renderer::mouse_to_map(world, self.console) {
+            Some(pt) => pt,
+            None => return,
+        };
+
+        let lines = {
+            let map = world.read_resource::<WorldMap>();
+            if !map.contains(pt) || !map[pt].visible {
+                return;
+            }
+
+            let positions = world.read_storage::<Position>();
+            let names = world.read_storage::<Name>();
+            let stats = world.read_storage::<CombatStats>();
+
+            (&world.entities(), &positions, &names)
+                .join()
+                .filter(|(_, &Position(p), _)| p == pt)
+                .map(|(e, _, Name(name))| match stats.get(e) {
+                    Some(stats) => format!("{} ({}/{} hp)", name, stats.hp, stats.max_hp),
+                    None => name.clone(),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        if lines.is_empty() {
+            return;
+        }
+
+        let center = *world.read_resource::<Point>();
+
+        if let Some(con) = world.write_storage::<CTM>().get_mut(self.console) {
+            let cell = match renderer::map_to_console(con, center, pt) {
+                Some(cell) => cell,
+                None => return,
+            };
+
+            let dims = *con.dimensions();
+            let w = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as u32 + 4;
+            let h = lines.len() as u32 + 2;
+
+            // Draw the tooltip to the right of the cursor, unless there is no room left
+            let x = if cell.x() + 2 + w <= dims[0] {
+                cell.x() + 2
+            } else {
+                cell.x().saturating_sub(w + 1)
+            };
+            let y = cell.y().min(dims[1].saturating_sub(h));
+
+            con.draw_box((x, y, w, h));
+            for (i, line) in lines.iter().enumerate() {
+                con.print((x + 2, y + i as u32 + 1), line);
+            }
+        }
     }
 
     // Updates the FPS counter with the currently measured FPS.
     fn update_fps_counter(&mut self, world: &mut World) {
+        if !world.read_resource::<Settings>().show_fps {
+            return;
+        }
+
         let fps = format!(
             "{:.0}",
             world.read_resource::<FpsCounter>().sampled_fps().round()
@@ -70,8 +153,8 @@ impl Ui {
 
                 con.draw_progress_bar(
                     (28, 43),
-                    51,
-                    stats.hp as u32,
+                    20,
+                    stats.hp.max(0) as u32,
                     stats.max_hp as u32,
                     Srgba::new(1., 0., 0., 1.),
                     Srgba::new(0.2, 0., 0., 1.),
@@ -80,6 +163,48 @@ impl Ui {
         }
     }
 
+    // Update the player's level, along with the progress towards the next one.
+    fn update_xp_display(&mut self, world: &mut World) {
+        if let Some(con) = world.write_storage::<CTM>().get_mut(self.console) {
+            let players = world.read_storage::<Player>();
+            let experience = world.read_storage::<Experience>();
+
+            if let Some((_, exp)) = (&players, &experience).join().next() {
+                con.print_color(
+                    (49, 43),
+                    format!(" Level {} ", exp.level),
+                    Srgba::new(1., 1., 0., 1.),
+                );
+
+                con.draw_progress_bar(
+                    (59, 43),
+                    20,
+                    exp.xp,
+                    exp.next_level(),
+                    Srgba::new(0., 0.6, 1., 1.),
+                    Srgba::new(0., 0.1, 0.2, 1.),
+                );
+            }
+        }
+    }
+
+    // Update the list of status effects the player is under, along with their remaining turns.
+    fn update_effects_display(&mut self, world: &mut World) {
+        if let Some(con) = world.write_storage::<CTM>().get_mut(self.console) {
+            let players = world.read_storage::<Player>();
+            let statuses = world.read_storage::<StatusEffects>();
+
+            if let Some((_, StatusEffects(effects))) = (&players, &statuses).join().next() {
+                let text = effects
+                    .iter()
+                    .map(|active| format!(" {} ({}) ", active.effect.name(), active.turns))
+                    .collect::<String>();
+
+                con.print_color((1, 49), text, Srgba::new(0., 1., 1., 1.));
+            }
+        }
+    }
+
     // Update the combat log to show the most recent messages.
     fn update_combat_log(&mut self, world: &mut World) {
         if let Some(con) = world.write_storage::<CTM>().get_mut(self.console) {
@@ -1,17 +1,32 @@
 use crate::{
     core::map::{TileKind, WorldMap},
     math::Point,
+    resources::TileDimension,
+    systems::GameBindings,
     utils,
 };
 
 use amethyst::{
-    core::math::Point3,
+    assets::{AssetStorage, Handle, Loader},
+    core::{
+        math::{Point3, Vector3},
+        transform::Transform,
+        Parent,
+    },
     ecs::Entity,
+    input::InputHandler,
     prelude::*,
-    renderer::palette::Srgba,
+    renderer::{palette::Srgba, Camera, ImageFormat, SpriteSheet, SpriteSheetFormat, Texture},
     tiles::{Map, MapStorage, MortonEncoder, Region, Tile, TileMap},
+    window::ScreenDimensions,
 };
 
+/// Width of the console, in tiles.
+pub const CONSOLE_WIDTH: u32 = 80;
+
+/// Height of the console, in tiles.
+pub const CONSOLE_HEIGHT: u32 = 50;
+
 /// `TileMap` alias for `ConsoleTile` type.
 pub type ConsoleTileMap = TileMap<ConsoleTile, MortonEncoder>;
 
@@ -70,12 +85,31 @@ pub fn refresh_map_view(world: &mut World, console: Entity) {
                         tile.glyph = Some(match state.kind {
                             TileKind::Floor => utils::to_glyph('.'),
                             TileKind::Wall => utils::to_glyph('#'),
+                            TileKind::DownStairs => utils::to_glyph('>'),
+                            TileKind::UpStairs => utils::to_glyph('<'),
+                            TileKind::ClosedDoor => utils::to_glyph('+'),
+                            TileKind::OpenDoor => utils::to_glyph('\''),
+                            TileKind::ShallowWater => utils::to_glyph('~'),
+                            TileKind::DeepWater | TileKind::Lava => utils::to_glyph('≈'),
+                            TileKind::Rubble => utils::to_glyph('░'),
+                            TileKind::TallGrass => utils::to_glyph('"'),
                         });
 
                         tile.tint = if state.visible {
                             match state.kind {
                                 TileKind::Floor => Srgba::new(0.2, 0.2, 0.2, 1.0),
                                 TileKind::Wall => Srgba::new(0.0, 0.17, 0.21, 1.0),
+                                TileKind::DownStairs | TileKind::UpStairs => {
+                                    Srgba::new(0.0, 0.8, 0.8, 1.0)
+                                }
+                                TileKind::ClosedDoor | TileKind::OpenDoor => {
+                                    Srgba::new(0.55, 0.35, 0.1, 1.0)
+                                }
+                                TileKind::ShallowWater => Srgba::new(0.2, 0.5, 0.9, 1.0),
+                                TileKind::DeepWater => Srgba::new(0.0, 0.1, 0.6, 1.0),
+                                TileKind::Lava => Srgba::new(0.9, 0.3, 0.0, 1.0),
+                                TileKind::Rubble => Srgba::new(0.4, 0.35, 0.3, 1.0),
+                                TileKind::TallGrass => Srgba::new(0.1, 0.6, 0.1, 1.0),
                             }
                         } else {
                             Srgba::new(0.05, 0.05, 0.05, 1.0)
@@ -88,3 +122,122 @@ pub fn refresh_map_view(world: &mut World, console: Entity) {
         }
     }
 }
+
+/// Converts a point in map coordinates to the console cell it is rendered on,
+/// given the map point the console is centered on.
+///
+/// Returns `None` if the point falls outside of the console.
+pub fn map_to_console(console: &ConsoleTileMap, center: Point, pt: Point) -> Option<Point> {
+    let dims = *console.dimensions();
+
+    // This is the inverse of the transformation performed in `refresh_map_view`
+    let x = pt.x() as i32 - center.x() as i32 + (dims[0] as i32) / 2;
+    let y = center.y() as i32 - pt.y() as i32 + (dims[1] as i32) - (dims[1] as i32) / 2 - 1;
+
+    if x < 0 || x >= dims[0] as i32 || y < 0 || y >= dims[1] as i32 {
+        None
+    } else {
+        Some(Point::new(x as u32, y as u32))
+    }
+}
+
+/// Returns the map point under the mouse cursor, if the cursor is over the console.
+///
+/// The returned point might still fall outside of the map.
+pub fn mouse_to_map(world: &World, console: Entity) -> Option<Point> {
+    let (mx, my) = world
+        .read_resource::<InputHandler<GameBindings>>()
+        .mouse_position()?;
+
+    let (screen_width, screen_height) = {
+        let dim = world.read_resource::<ScreenDimensions>();
+        (dim.width(), dim.height())
+    };
+
+    let tile_dim = world.read_resource::<TileDimension>().0 as f32;
+    let center = *world.read_resource::<Point>();
+    let dims = *world
+        .read_storage::<ConsoleTileMap>()
+        .get(console)?
+        .dimensions();
+
+    // The camera is centered on the console, and screen coordinates grow right-down
+    let col = ((mx - screen_width / 2.) / tile_dim).floor() as i32 + (dims[0] as i32) / 2;
+    let row = ((my - screen_height / 2.) / tile_dim).floor() as i32 + (dims[1] as i32) / 2;
+
+    if col < 0 || col >= dims[0] as i32 || row < 0 || row >= dims[1] as i32 {
+        return None;
+    }
+
+    // This is the inverse of the transformation performed in `map_to_console`
+    let x = center.x() as i32 - (dims[0] as i32) / 2 + col;
+    let y = center.y() as i32 + (dims[1] as i32) - (dims[1] as i32) / 2 - row - 1;
+
+    if x < 0 || y < 0 {
+        None
+    } else {
+        Some(Point::new(x as u32, y as u32))
+    }
+}
+
+/// Allocates a `TileMap` for the console emulation, attached to the given pivot.
+pub fn create_console(world: &mut World, pivot: Entity, sheet: Handle<SpriteSheet>) -> Entity {
+    let tile_dim = world.read_resource::<TileDimension>().0;
+
+    let tilemap = ConsoleTileMap::new(
+        Vector3::new(CONSOLE_WIDTH, CONSOLE_HEIGHT, 1),
+        Vector3::new(tile_dim, tile_dim, 1),
+        Some(sheet),
+    );
+
+    // Align tilemap to pivot
+    let mut transform = Transform::default();
+    transform.set_translation_xyz(0., -(tile_dim as f32), 0.);
+
+    world
+        .create_entity()
+        .with(Parent::new(pivot))
+        .with(transform)
+        .with(tilemap)
+        .build()
+}
+
+/// Creates an orthographic camera covering the entire screen view, centered on the given pivot.
+pub fn spawn_camera(world: &mut World, pivot: Entity) -> Entity {
+    let tile_dim = world.read_resource::<TileDimension>().0 as f32;
+
+    let (screen_width, screen_height) = {
+        let dim = world.read_resource::<ScreenDimensions>();
+        (dim.width(), dim.height())
+    };
+
+    // Put the camera 10 units away from the console, and center it on the pivot
+    let mut transform = Transform::default();
+    transform.set_translation_xyz(-tile_dim / 2., -tile_dim / 2., 10.);
+
+    world
+        .create_entity()
+        .with(Parent::new(pivot))
+        .with(transform)
+        .with(Camera::standard_2d(screen_width, screen_height))
+        .build()
+}
+
+/// Loads an image and the corresponding RON file as a spritesheet.
+pub fn load_sprite_sheet(world: &mut World, png_path: &str, ron_path: &str) -> Handle<SpriteSheet> {
+    let texture_handle = {
+        let loader = world.read_resource::<Loader>();
+        let texture_storage = world.read_resource::<AssetStorage<Texture>>();
+        loader.load(png_path, ImageFormat::default(), (), &texture_storage)
+    };
+
+    let loader = world.read_resource::<Loader>();
+    let sprite_sheet_store = world.read_resource::<AssetStorage<SpriteSheet>>();
+
+    loader.load(
+        ron_path,
+        SpriteSheetFormat(texture_handle),
+        (),
+        &sprite_sheet_store,
+    )
+}
@@ -7,7 +7,7 @@ mod states;
 mod systems;
 mod utils;
 
-use crate::{graphics::renderer::ConsoleTile, states::RunState, systems::*};
+use crate::{graphics::renderer::ConsoleTile, states::MainMenuState, systems::*};
 
 use amethyst::{
     core::transform::TransformBundle,
@@ -22,6 +22,7 @@ use amethyst::{
     utils::{application_root_dir, fps_counter::FpsCounterBundle},
 };
 use states::{GameStateEvent, GameStateEventReader, GameStateWrapper};
+use std::env;
 
 fn main() -> amethyst::Result<()> {
     amethyst::start_logger(Default::default());
@@ -51,9 +52,16 @@ fn main() -> amethyst::Result<()> {
                 .with_plugin(RenderTiles2D::<ConsoleTile, MortonEncoder>::default()),
         )?;
 
+    // An explicit `--seed <n>` starts a new, reproducible game right away
+    let seed = env::args()
+        .skip_while(|arg| arg != "--seed")
+        .nth(1)
+        .map(|seed| seed.parse::<u64>())
+        .transpose()?;
+
     let mut game = CoreApplication::<'_, _, GameStateEvent, GameStateEventReader>::new(
         assets_dir,
-        GameStateWrapper::new(RunState::default()),
+        GameStateWrapper::new(MainMenuState::new(seed)),
         game_data,
     )?;
 
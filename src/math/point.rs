@@ -1,9 +1,11 @@
 use amethyst::core::math::Point2;
 
+use serde::{Deserialize, Serialize};
 use std::ops::{Add, Index, IndexMut};
 
 /// 2D point in the game world.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "(u32, u32)", into = "(u32, u32)")]
 pub struct Point(Point2<u32>);
 
 impl Default for Point {
@@ -53,6 +55,12 @@ impl From<(u32, u32)> for Point {
     }
 }
 
+impl From<Point> for (u32, u32) {
+    fn from(p: Point) -> Self {
+        (p.x(), p.y())
+    }
+}
+
 impl Index<usize> for Point {
     type Output = u32;
 
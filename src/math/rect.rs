@@ -1,7 +1,9 @@
 use super::Point;
 
+use serde::{Deserialize, Serialize};
+
 /// A rectangle in the game world.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct Rect {
     bl: Point,
     tr: Point,
@@ -54,6 +56,13 @@ impl Rect {
             && self.top() >= other.bottom()
     }
 
+    /// Returns all the points strictly inside this rectangle, ie. excluding its sides.
+    pub fn inner_points(&self) -> Vec<Point> {
+        (self.bottom() + 1..self.top())
+            .flat_map(|y| (self.left() + 1..self.right()).map(move |x| Point::new(x, y)))
+            .collect()
+    }
+
     /// Returns the center of this rectangle.
     pub fn center(&self) -> Point {
         Point::new(
@@ -1,11 +1,14 @@
 //! This module contains all the resources used by the ECS.
 
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+
 /// Resource holding the side length of a tile.
 #[derive(Default)]
 pub struct TileDimension(pub u32);
 
 /// Resource holding the combat log.
-#[derive(Default)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct CombatLog(Vec<String>);
 
 impl CombatLog {
@@ -19,3 +22,196 @@ impl CombatLog {
         &self.0
     }
 }
+
+/// Resource holding a message for the player to read on the main menu,
+/// such as the reason why a run could not start.
+#[derive(Default)]
+pub struct MenuNotice(pub Option<String>);
+
+/// Resource holding the depth of the current dungeon level, starting from 1.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Depth(pub u32);
+
+impl Default for Depth {
+    fn default() -> Self {
+        Depth(1)
+    }
+}
+
+/// Resource signaling that the player wants to move to another dungeon level.
+#[derive(Copy, Clone, PartialEq)]
+pub enum LevelTransition {
+    None,
+    Descend,
+    Ascend,
+}
+
+impl Default for LevelTransition {
+    fn default() -> Self {
+        LevelTransition::None
+    }
+}
+
+/// Resource signaling that the player has just reached the given experience level.
+#[derive(Default)]
+pub struct LevelUp(pub Option<u32>);
+
+/// Resource holding statistics about the current run.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct RunStats {
+    pub turns: u32,
+    pub kills: u32,
+    pub killed_by: Option<String>,
+}
+
+/// Resource holding the player's preferences, which outlive any single run.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Settings {
+    pub show_fps: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings { show_fps: true }
+    }
+}
+
+/// Resource holding the random number generator used to build the game world.
+///
+/// Map generation and entity spawning draw all their randomness from here,
+/// so that the same seed always yields the same dungeon.
+///
+/// Every number is drawn from a whole number of 32 bit words, so that the generator can
+/// be resumed from its [`RngState`] just by drawing the same amount of words once again.
+pub struct SeededRng {
+    seed: u64,
+    state: RngState,
+    rng: StdRng,
+}
+
+/// Position of a [`SeededRng`] within its streams, which is all it takes to resume it.
+#[derive(Default, Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RngState {
+    /// Depth of the level whose stream is being drawn from, or zero before the first one.
+    pub level: u32,
+    /// Number of words drawn from the stream so far.
+    pub draws: u64,
+}
+
+impl SeededRng {
+    /// Creates a new generator from the given seed.
+    pub fn new(seed: u64) -> SeededRng {
+        SeededRng::resume(seed, RngState::default())
+    }
+
+    /// Creates a generator from the given seed, and brings it to the given state.
+    pub fn resume(seed: u64, state: RngState) -> SeededRng {
+        let mut rng = SeededRng {
+            seed,
+            state,
+            rng: StdRng::seed_from_u64(seed ^ u64::from(state.level)),
+        };
+
+        for _ in 0..state.draws {
+            rng.rng.next_u32();
+        }
+
+        rng
+    }
+
+    /// Returns the seed this generator was created from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the current state of the generator, to be resumed later.
+    pub fn state(&self) -> RngState {
+        self.state
+    }
+
+    /// Restarts the generator from a seed derived from the run's seed and the given depth.
+    ///
+    /// Each level is then built the same way no matter what was drawn before it.
+    pub fn enter_level(&mut self, depth: u32) {
+        *self = SeededRng::resume(
+            self.seed,
+            RngState {
+                level: depth,
+                draws: 0,
+            },
+        );
+    }
+}
+
+impl Default for SeededRng {
+    fn default() -> Self {
+        SeededRng::new(rand::random())
+    }
+}
+
+impl RngCore for SeededRng {
+    fn next_u32(&mut self) -> u32 {
+        self.state.draws += 1;
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let low = u64::from(self.next_u32());
+        let high = u64::from(self.next_u32());
+        (high << 32) | low
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let word = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&word[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn resumed_generator_continues_the_same_stream() {
+        let mut rng = SeededRng::new(1234);
+        rng.enter_level(3);
+
+        let _: u32 = rng.gen();
+        let _: u64 = rng.gen();
+        let _ = rng.gen_range(0, 100);
+        let mut bytes = [0; 7];
+        rng.fill_bytes(&mut bytes);
+
+        // The state goes through the save file just like the rest of the game
+        let saved = ron::ser::to_string(&rng.state()).unwrap();
+        let state = ron::de::from_str(&saved).unwrap();
+        let mut resumed = SeededRng::resume(rng.seed(), state);
+
+        assert_eq!(resumed.state(), rng.state());
+        for _ in 0..100 {
+            assert_eq!(resumed.next_u64(), rng.next_u64());
+        }
+    }
+
+    #[test]
+    fn levels_do_not_depend_on_earlier_draws() {
+        let mut fresh = SeededRng::new(99);
+        fresh.enter_level(2);
+
+        let mut used = SeededRng::new(99);
+        for _ in 0..50 {
+            used.next_u32();
+        }
+        used.enter_level(2);
+
+        assert_eq!(fresh.next_u64(), used.next_u64());
+    }
+}
@@ -3,30 +3,30 @@
 
 use crate::{
     components::*,
-    core::{map::WorldMap, spawn},
-    graphics::{
-        renderer::{self, ConsoleTileMap},
-        Ui,
+    core::{
+        map::{self, Level, TileKind, WorldMap},
+        raws::Raws,
+        save::{self, LevelCache},
+        spawn,
     },
-    math::{Point, Rect},
-    resources::{CombatLog, TileDimension},
-    states::{GameState, GameStateEvent, GameTrans},
+    graphics::{renderer, Ui},
+    math::Point,
+    resources::{
+        CombatLog, Depth, LevelTransition, LevelUp, MenuNotice, RunStats, SeededRng, TileDimension,
+    },
+    states::{GameOverState, GameState, GameStateEvent, GameStateWrapper, GameTrans, LevelUpState},
     systems::*,
 };
 
 use amethyst::{
-    assets::{AssetStorage, Handle, Loader},
-    core::{math::Vector3, transform::Transform, Parent},
-    ecs::{Dispatcher, DispatcherBuilder, Entity},
+    assets::Handle,
+    ecs::{Dispatcher, DispatcherBuilder, Entity, Join},
     input::{is_close_requested, InputEvent},
     prelude::*,
-    renderer::{Camera, ImageFormat, SpriteSheet, SpriteSheetFormat, Texture},
-    window::ScreenDimensions,
+    renderer::SpriteSheet,
 };
-use rand::Rng;
-
-const CONSOLE_WIDTH: u32 = 80;
-const CONSOLE_HEIGHT: u32 = 50;
+use rand::{seq::SliceRandom, Rng};
+use std::{mem, path::PathBuf};
 
 const MAP_WIDTH: u32 = 80;
 const MAP_HEIGHT: u32 = 50;
@@ -38,31 +38,77 @@ pub struct RunState<'a, 'b> {
     console: Option<Entity>,
     input: RunStateInputDispatcher,
     dispatcher: Option<Dispatcher<'a, 'b>>,
+    sheet: Option<Handle<SpriteSheet>>,
+    load_from: Option<PathBuf>,
+    seed: Option<u64>,
+    aborted: bool,
+}
+
+impl<'a, 'b> RunState<'a, 'b> {
+    /// Creates a game state which starts a new game from the given seed.
+    pub fn from_seed(seed: u64) -> Self {
+        RunState {
+            seed: Some(seed),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a game state which resumes the game saved at the given path.
+    pub fn from_save(path: PathBuf) -> Self {
+        RunState {
+            load_from: Some(path),
+            ..Default::default()
+        }
+    }
 }
 
 impl<'a, 'b> GameState for RunState<'a, 'b> {
     fn on_start(&mut self, StateData { world, .. }: StateData<'_, GameData>) {
+        // Without the monster and item templates there is no run to speak of
+        let raws = match Raws::load() {
+            Ok(raws) => raws,
+            Err(e) => {
+                let message = format!("Could not load the raws: {}", e);
+                amethyst::log::error!("{}", message);
+                world.insert(MenuNotice(Some(message)));
+                self.aborted = true;
+                return;
+            }
+        };
+
         // Setup systems for this state
         let mut dispatcher = DispatcherBuilder::new()
             .with(MapIndexingSystem, "map_indexing", &[])
             .with(VisibilitySystem, "visibility", &[])
-            .with(MonsterAI, "monster_ai", &["visibility"])
+            .with(MonsterAI::default(), "monster_ai", &["visibility"])
+            .with(AutoMoveSystem, "auto_move", &["visibility"])
             .with(
                 MoveResolver,
                 "move_resolver",
-                &["monster_ai", "map_indexing"],
+                &["monster_ai", "auto_move", "map_indexing"],
             )
             .with(PickUpSystem, "pick_up", &["move_resolver"])
             .with(ItemUsageResolver, "item_usage_resolver", &["move_resolver"])
             .with(ItemDropResolver, "item_drop_resolver", &["move_resolver"])
-            .with(MeleeCombatResolver, "melee_resolver", &["move_resolver"])
-            .with(DamageResolver, "damage_resolver", &["melee_resolver"])
+            .with(EquipResolver, "equip_resolver", &["item_drop_resolver"])
+            .with(
+                MeleeCombatResolver,
+                "melee_resolver",
+                &["move_resolver", "equip_resolver"],
+            )
+            .with(
+                DamageResolver,
+                "damage_resolver",
+                &["melee_resolver", "item_usage_resolver"],
+            )
+            .with(LevelUpSystem, "level_up", &["damage_resolver"])
             .with(
                 PositionTranslator,
                 "position_translator",
                 &["move_resolver"],
             )
-            .with(TurnSystem::default(), "turn", &["position_translator"])
+            .with(TurnSystem, "turn", &["position_translator"])
+            .with(StatusEffectSystem::default(), "status_effects", &["turn"])
             .build();
 
         dispatcher.setup(world);
@@ -71,33 +117,58 @@ impl<'a, 'b> GameState for RunState<'a, 'b> {
 
         // Create required resources
         world.insert(TileDimension(20));
+        world.insert(RunStats::default());
         world.insert({
             let mut log = CombatLog::default();
             log.push("Welcome to Mistery!");
             log
         });
+        world.insert(raws);
 
         // Register components that are not used in any system.
         world.register::<Pickable>();
         world.register::<Ranged>();
-        world.register::<InflictsDamage>();
+        world.register::<Description>();
 
         // Load spritesheet
-        let sprite_sheet =
-            load_sprite_sheet(world, "texture/cp437_20x20.png", "texture/cp437_20x20.ron");
-
-        // Initialize world map (*must* come before everything else)
-        world.insert(WorldMap::rooms_and_corridors(MAP_WIDTH, MAP_HEIGHT));
-
-        // Initialize all the game-related entities
-        let player = spawn_entities(world, sprite_sheet.clone());
+        let sprite_sheet = renderer::load_sprite_sheet(
+            world,
+            "texture/cp437_20x20.png",
+            "texture/cp437_20x20.ron",
+        );
+
+        // New games without an explicit seed get a random one
+        let seed = self.seed.unwrap_or_else(rand::random);
+
+        // Either resume a saved game or initialize all the game-related entities
+        let player = match self.load_from.take() {
+            Some(path) => match save::load_game(world, &path, sprite_sheet.clone()) {
+                Ok(player) => {
+                    world
+                        .write_resource::<CombatLog>()
+                        .push("Welcome back to Mistery!");
+                    player
+                }
+                Err(e) => {
+                    world
+                        .write_resource::<CombatLog>()
+                        .push(format!("Could not load the saved game: {}", e));
+                    new_game(world, seed, sprite_sheet.clone())
+                }
+            },
+            None => new_game(world, seed, sprite_sheet.clone()),
+        };
+
+        // Finally, create the camera
+        renderer::spawn_camera(world, player);
 
         // Allocate console tilemap for rendering
-        let console = create_console(world, player, sprite_sheet);
+        let console = renderer::create_console(world, player, sprite_sheet.clone());
 
         // Load UI
         self.ui = Some(Ui::new(console));
         self.console = Some(console);
+        self.sheet = Some(sprite_sheet);
     }
 
     fn handle_event(
@@ -107,6 +178,11 @@ impl<'a, 'b> GameState for RunState<'a, 'b> {
     ) -> GameTrans {
         match &event {
             StateEvent::Window(event) if is_close_requested(&event) => Trans::Quit,
+            _ if self.aborted => Trans::None,
+            StateEvent::Input(InputEvent::ActionPressed(ActionBinding::SaveGame)) => {
+                save_game(world);
+                Trans::None
+            }
             StateEvent::Input(InputEvent::ActionPressed(action)) => {
                 self.input.handle(world, self.console.unwrap(), *action)
             }
@@ -114,11 +190,67 @@ impl<'a, 'b> GameState for RunState<'a, 'b> {
         }
     }
 
+    fn on_stop(&mut self, StateData { world, .. }: StateData<'_, GameData>) {
+        // Runs which are still going are saved, so that they can be resumed later
+        if !self.aborted && !is_player_dead(world) {
+            save_game(world);
+        }
+
+        // Tear down the whole game world, so that a new run can start from scratch
+        world.delete_all();
+
+        self.ui = None;
+        self.console = None;
+        self.dispatcher = None;
+        self.sheet = None;
+    }
+
     fn update(&mut self, StateData { world, .. }: &mut StateData<'_, GameData>) -> GameTrans {
+        // Runs which could not even start go straight back to the main menu
+        if self.aborted {
+            return Trans::Pop;
+        }
+
         if let Some(dispatcher) = &mut self.dispatcher {
             dispatcher.dispatch(world);
         }
-        Trans::None
+
+        // Move to another level, if the player has asked to
+        let transition = mem::replace(
+            &mut *world.write_resource::<LevelTransition>(),
+            LevelTransition::None,
+        );
+        if let Some(sheet) = &self.sheet {
+            let depth = world.read_resource::<Depth>().0;
+            match transition {
+                LevelTransition::Descend => change_level(world, depth + 1, sheet.clone()),
+                LevelTransition::Ascend => change_level(world, depth - 1, sheet.clone()),
+                LevelTransition::None => (),
+            }
+        }
+
+        let level_up = world.write_resource::<LevelUp>().0.take();
+
+        // The player is never deleted on death, its HP are checked instead
+        match self.console {
+            Some(console) if is_player_dead(world) => {
+                // Dead is dead: the run cannot be resumed anymore
+                if let Err(e) = save::delete_save() {
+                    world
+                        .write_resource::<CombatLog>()
+                        .push(format!("Could not delete the saved game: {}", e));
+                }
+
+                Trans::Push(Box::new(GameStateWrapper::new(GameOverState::new(console))))
+            }
+            Some(console) => match level_up {
+                Some(level) => Trans::Push(Box::new(GameStateWrapper::new(LevelUpState::new(
+                    console, level,
+                )))),
+                None => Trans::None,
+            },
+            None => Trans::None,
+        }
     }
 
     fn shadow_update(&mut self, StateData { world, .. }: StateData<'_, GameData>) {
@@ -131,121 +263,142 @@ impl<'a, 'b> GameState for RunState<'a, 'b> {
     }
 }
 
-// Allocates a `TileMap` for the console emulation.
-fn create_console(world: &mut World, pivot: Entity, sheet: Handle<SpriteSheet>) -> Entity {
-    let tile_dim = world.read_resource::<TileDimension>().0;
-
-    let tilemap = ConsoleTileMap::new(
-        Vector3::new(CONSOLE_WIDTH, CONSOLE_HEIGHT, 1),
-        Vector3::new(tile_dim, tile_dim, 1),
-        Some(sheet),
-    );
+// Returns whether the player has died.
+fn is_player_dead(world: &World) -> bool {
+    (
+        &world.read_storage::<Player>(),
+        &world.read_storage::<CombatStats>(),
+    )
+        .join()
+        .any(|(_, stats)| stats.hp <= 0)
+}
 
-    // Align tilemap to pivot
-    let mut transform = Transform::default();
-    transform.set_translation_xyz(0., -(tile_dim as f32), 0.);
+// Saves the current game, reporting the outcome in the combat log.
+fn save_game(world: &mut World) {
+    let msg = match save::save_path().and_then(|path| save::save_game(world, &path)) {
+        Ok(()) => String::from("Game saved."),
+        Err(e) => format!("Could not save the game: {}", e),
+    };
+    world.write_resource::<CombatLog>().push(msg);
+}
 
+// Generates a new map from the given seed and populates it. Returns the player entity.
+fn new_game(world: &mut World, seed: u64, sheet: Handle<SpriteSheet>) -> Entity {
+    world.insert(SeededRng::new(seed));
+    world.insert(Depth::default());
+    world.insert(GameClock::default());
+    world.insert(LevelCache::default());
     world
-        .create_entity()
-        .with(Parent::new(pivot))
-        .with(transform)
-        .with(tilemap)
-        .build()
-}
+        .write_resource::<CombatLog>()
+        .push(format!("Dungeon seed: {}", seed));
 
-// Spawns the player, the monsters and the camera. Returns the player entity.
-fn spawn_entities(world: &mut World, sheet: Handle<SpriteSheet>) -> Entity {
-    // Iterator over all the map rooms
-    let mut rooms = world
-        .read_resource::<WorldMap>()
-        .rooms()
-        .to_vec()
-        .into_iter();
-
-    // Spawn the player in the middle of the first room.
-    let player = spawn::player(world, rooms.next().unwrap().center(), sheet.clone());
-
-    // Spawn random monsters in all the other rooms
-    for room in rooms {
-        spawn_room(world, room, sheet.clone());
-    }
+    // Initialize world map (*must* come before everything else)
+    let Level {
+        map,
+        start,
+        spawn_regions,
+    } = generate_level(world, Depth::default().0);
+    world.insert(map);
 
-    // Finally, create the camera
-    let (screen_width, screen_height) = {
-        let dim = world.read_resource::<ScreenDimensions>();
-        (dim.width(), dim.height())
-    };
-    spawn_camera(world, player, screen_width, screen_height);
+    let player = spawn::player(world, start, sheet.clone());
+    spawn_level(world, &spawn_regions, Depth::default().0, sheet);
 
     player
 }
 
-// Spawns random entities in a room. This includes monsters and items.
-fn spawn_room(world: &mut World, room: Rect, sheet: Handle<SpriteSheet>) {
-    let mut rng = rand::thread_rng();
+// Moves the player to the level at the given depth.
+// The current level is stashed away, and levels which have already been visited
+// are restored as they were left, while new ones are generated from scratch.
+fn change_level(world: &mut World, to: u32, sheet: Handle<SpriteSheet>) {
+    let from = world.read_resource::<Depth>().0;
+    save::stash_level(world, from);
+    world.write_resource::<Depth>().0 = to;
+
+    let arrival = if save::restore_level(world, to, sheet.clone()) {
+        // Taking the stairs leads to the other end of the same stairs
+        let kind = if to < from {
+            TileKind::DownStairs
+        } else {
+            TileKind::UpStairs
+        };
+        world.read_resource::<WorldMap>().find(kind)
+    } else {
+        let Level {
+            map,
+            start,
+            spawn_regions,
+        } = generate_level(world, to);
+        world.insert(map);
+        spawn_level(world, &spawn_regions, to, sheet);
+        Some(start)
+    };
 
-    let n_monsters = rng.gen_range(0, spawn::MAX_MONSTERS + 1);
-    let n_items = rng.gen_range(0, spawn::MAX_ITEMS + 1);
+    if let Some(pos) = arrival {
+        place_player(world, pos);
+    }
 
-    // Compute spawn points for both items and monsters
-    let mut spawn_points = Vec::with_capacity(n_monsters + n_items);
-    for _ in 0..spawn_points.capacity() {
-        loop {
-            let x = rng.gen_range(room.left() + 1, room.right());
-            let y = rng.gen_range(room.bottom() + 1, room.top());
-            let pt = Point::new(x, y);
+    let verb = if to < from { "climb" } else { "descend" };
+    world
+        .write_resource::<CombatLog>()
+        .push(format!("You {} to depth {}.", verb, to));
+}
 
-            if !spawn_points.contains(&pt) {
-                spawn_points.push(pt);
-                break;
-            }
-        }
-    }
+// Generates the level at the given depth, from the generator stream reserved to it.
+fn generate_level(world: &mut World, depth: u32) -> Level {
+    let mut rng = world.write_resource::<SeededRng>();
+    rng.enter_level(depth);
+    map::generate_level(MAP_WIDTH, MAP_HEIGHT, depth, &mut *rng)
+}
 
-    let (monster_spawns, item_spawns) = spawn_points.split_at(n_monsters);
+// Moves the player to the given point of the current map.
+fn place_player(world: &mut World, pos: Point) {
+    let players = world.read_storage::<Player>();
+    let mut positions = world.write_storage::<Position>();
+    let mut viewsheds = world.write_storage::<Viewshed>();
 
-    // Spawn monsters
-    for pt in monster_spawns {
-        spawn::random_monster(world, *pt, sheet.clone());
+    for (_, Position(p), vs) in (&players, &mut positions, &mut viewsheds).join() {
+        *p = pos;
+        vs.dirty = true;
     }
 
-    // Spawn items
-    for pt in item_spawns {
-        spawn::random_item(world, *pt, sheet.clone());
+    *world.write_resource::<Point>() = pos;
+}
+
+// Populates the spawn regions of a level with monsters and items fit for its depth.
+fn spawn_level(world: &mut World, regions: &[Vec<Point>], depth: u32, sheet: Handle<SpriteSheet>) {
+    for region in regions {
+        spawn_region(world, region, depth, sheet.clone());
     }
 }
 
-// Creates an orthographic camera covering the entire screen view.
-fn spawn_camera(world: &mut World, pivot: Entity, screen_width: f32, screen_height: f32) {
-    let tile_dim = world.read_resource::<TileDimension>().0 as f32;
+// Spawns random entities in a region of the map. This includes monsters and items.
+// How crowded regions get at each depth is decided by the raws.
+fn spawn_region(world: &mut World, region: &[Point], depth: u32, sheet: Handle<SpriteSheet>) {
+    let (n_monsters, spawn_points) = {
+        let raws = world.read_resource::<Raws>();
+        let mut rng = world.write_resource::<SeededRng>();
 
-    // Put the camera 10 units away from the console, and center it on the pivot
-    let mut transform = Transform::default();
-    transform.set_translation_xyz(-tile_dim / 2., -tile_dim / 2., 10.);
+        let n_monsters = rng.gen_range(0, raws.monsters_per_region.max_at(depth) + 1) as usize;
+        let n_items = rng.gen_range(0, raws.items_per_region.max_at(depth) + 1) as usize;
 
-    world
-        .create_entity()
-        .with(Parent::new(pivot))
-        .with(transform)
-        .with(Camera::standard_2d(screen_width, screen_height))
-        .build();
-}
+        // Compute distinct spawn points for both items and monsters
+        let spawn_points = region
+            .choose_multiple(&mut *rng, n_monsters + n_items)
+            .copied()
+            .collect::<Vec<_>>();
 
-// Loads an images and corresponding RON file as spritesheet.
-fn load_sprite_sheet(world: &mut World, png_path: &str, ron_path: &str) -> Handle<SpriteSheet> {
-    let texture_handle = {
-        let loader = world.read_resource::<Loader>();
-        let texture_storage = world.read_resource::<AssetStorage<Texture>>();
-        loader.load(png_path, ImageFormat::default(), (), &texture_storage)
+        (n_monsters.min(spawn_points.len()), spawn_points)
     };
 
-    let loader = world.read_resource::<Loader>();
-    let sprite_sheet_store = world.read_resource::<AssetStorage<SpriteSheet>>();
+    let (monster_spawns, item_spawns) = spawn_points.split_at(n_monsters);
+
+    // Spawn monsters
+    for pt in monster_spawns {
+        spawn::random_monster(world, *pt, depth, sheet.clone());
+    }
 
-    loader.load(
-        ron_path,
-        SpriteSheetFormat(texture_handle),
-        (),
-        &sprite_sheet_store,
-    )
+    // Spawn items
+    for pt in item_spawns {
+        spawn::random_item(world, *pt, depth, sheet.clone());
+    }
 }
@@ -0,0 +1,133 @@
+use crate::{
+    components::*,
+    graphics::{console::Console, renderer::ConsoleTileMap},
+    resources::{RunStats, SeededRng},
+    states::{GameState, GameStateEvent, GameStateWrapper, GameTrans, RunState},
+};
+
+use amethyst::{
+    ecs::{Entity, Join},
+    input::{is_close_requested, InputEvent},
+    prelude::*,
+    renderer::palette::Srgba,
+};
+
+/// Game state shown when the player dies, summarizing the run.
+pub struct GameOverState {
+    console: Entity,
+    summary: Vec<String>,
+}
+
+impl GameOverState {
+    pub fn new(console: Entity) -> GameOverState {
+        GameOverState {
+            console,
+            summary: Vec::new(),
+        }
+    }
+}
+
+impl GameState for GameOverState {
+    fn on_start(&mut self, StateData { world, .. }: StateData<'_, GameData>) {
+        let stats = world.read_resource::<RunStats>();
+        let seed = world.read_resource::<SeededRng>().seed();
+
+        let items = {
+            let players = world.read_storage::<Player>();
+            let stored = world.read_storage::<InBackpack>();
+            let named = world.read_storage::<Name>();
+
+            (&stored, &named)
+                .join()
+                .filter(|(InBackpack { owner }, _)| players.contains(*owner))
+                .map(|(_, Name(name))| name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let level = (
+            &world.read_storage::<Player>(),
+            &world.read_storage::<Experience>(),
+        )
+            .join()
+            .map(|(_, exp)| exp.level)
+            .next()
+            .unwrap_or(1);
+
+        self.summary = vec![
+            format!(
+                "You were killed by {}.",
+                stats.killed_by.as_deref().unwrap_or("something")
+            ),
+            format!("You reached level {}.", level),
+            format!("You survived for {} turns.", stats.turns),
+            format!("You killed {} monsters.", stats.kills),
+            format!("You were carrying {} items:", items.len()),
+        ];
+
+        self.summary
+            .extend(items.into_iter().map(|item| format!("  {}", item)));
+
+        self.summary.push(String::new());
+        self.summary.push(format!("Dungeon seed: {}", seed));
+    }
+
+    fn update(&mut self, StateData { world, .. }: &mut StateData<'_, GameData>) -> GameTrans {
+        let count = self.summary.len() as u32;
+        let (x, y, w, h) = (20, 10, 40, count + 7);
+
+        let title_col = Srgba::new(1., 0., 0., 1.);
+        let text_col = Srgba::new(1., 1., 1., 1.);
+        let key_col = Srgba::new(1., 1., 0., 1.);
+
+        if let Some(con) = world
+            .write_storage::<ConsoleTileMap>()
+            .get_mut(self.console)
+        {
+            con.draw_box((x, y, w, h));
+
+            con.print_color((x + 2, y), " Game Over ", title_col);
+
+            for (i, line) in self.summary.iter().enumerate() {
+                con.print_color((x + 2, y + i as u32 + 2), line, text_col);
+            }
+
+            for (i, (key, text)) in [('n', "New game"), ('m', "Main menu"), ('q', "Quit")]
+                .iter()
+                .enumerate()
+            {
+                let y = y + count + 3 + i as u32;
+                con.put((x + 2, y), '(', text_col);
+                con.put((x + 3, y), *key, key_col);
+                con.put((x + 4, y), ')', text_col);
+                con.print((x + 6, y), text);
+            }
+        }
+
+        Trans::None
+    }
+
+    fn on_stop(&mut self, StateData { world, .. }: StateData<'_, GameData>) {
+        if let Some(con) = world
+            .write_storage::<ConsoleTileMap>()
+            .get_mut(self.console)
+        {
+            con.clear();
+        }
+    }
+
+    fn handle_event(&mut self, _: StateData<'_, GameData>, event: GameStateEvent) -> GameTrans {
+        match &event {
+            StateEvent::Window(event) if is_close_requested(&event) => Trans::Quit,
+            StateEvent::Input(InputEvent::KeyTyped('n')) => Trans::Sequence(vec![
+                Trans::Pop,
+                Trans::Switch(Box::new(GameStateWrapper::new(RunState::default()))),
+            ]),
+            // Pop both this state and the finished run, back to the main menu
+            StateEvent::Input(InputEvent::KeyTyped('m')) => {
+                Trans::Sequence(vec![Trans::Pop, Trans::Pop])
+            }
+            StateEvent::Input(InputEvent::KeyTyped('q')) => Trans::Quit,
+            _ => Trans::None,
+        }
+    }
+}
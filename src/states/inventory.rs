@@ -1,7 +1,7 @@
 use crate::{
     components::*,
     graphics::{console::Console, renderer::ConsoleTileMap},
-    states::{GameState, GameStateEvent, GameTrans},
+    states::{GameState, GameStateEvent, GameStateWrapper, GameTrans, TargetingState},
     systems::ActionBinding,
 };
 
@@ -15,6 +15,7 @@ use amethyst::{
 pub enum Intent {
     UseItem,
     DropItem,
+    Equip,
 }
 
 pub struct InventoryState {
@@ -41,14 +42,16 @@ impl GameState for InventoryState {
             let players = world.read_storage::<Player>();
             let stored = world.read_storage::<InBackpack>();
             let named = world.read_storage::<Name>();
+            let equipped = world.read_storage::<Equipped>();
 
             (&entities, &stored, &named)
                 .join()
-                .filter_map(|(item, InBackpack { owner }, Name(name))| {
-                    if players.contains(*owner) {
-                        Some((item, name.clone()))
+                .filter(|(_, InBackpack { owner }, _)| players.contains(*owner))
+                .map(|(item, _, Name(name))| {
+                    if equipped.contains(item) {
+                        (item, format!("{} (equipped)", name))
                     } else {
-                        None
+                        (item, name.clone())
                     }
                 })
                 .collect::<Vec<_>>()
@@ -109,18 +112,50 @@ impl GameState for InventoryState {
                         .next()
                     {
                         match self.intent {
+                            // Equipment is put on or taken off rather than used
+                            Intent::UseItem
+                                if world.read_storage::<Equippable>().contains(*what) =>
+                            {
+                                toggle_equipment(world, player, *what);
+                            }
                             Intent::UseItem => {
+                                // Ranged items require a target to be selected first
+                                if let Some(Ranged { range }) =
+                                    world.read_storage::<Ranged>().get(*what)
+                                {
+                                    return Trans::Switch(Box::new(GameStateWrapper::new(
+                                        TargetingState::new(*what, *range, self.console),
+                                    )));
+                                }
+
                                 world
                                     .write_storage()
-                                    .insert(player, WantsToUseItem { what: *what })
+                                    .insert(
+                                        player,
+                                        WantsToUseItem {
+                                            what: *what,
+                                            target: None,
+                                        },
+                                    )
                                     .unwrap();
+                                ActsOnTurns::spend(
+                                    &mut world.write_storage(),
+                                    player,
+                                    Action::UseItem,
+                                );
                             }
                             Intent::DropItem => {
                                 world
                                     .write_storage()
                                     .insert(player, WantsToDropItem { what: *what })
                                     .unwrap();
+                                ActsOnTurns::spend(
+                                    &mut world.write_storage(),
+                                    player,
+                                    Action::DropItem,
+                                );
                             }
+                            Intent::Equip => toggle_equipment(world, player, *what),
                         }
                         Trans::Pop
                     } else {
@@ -134,3 +169,19 @@ impl GameState for InventoryState {
         }
     }
 }
+
+// Makes the player take off the given item if it is equipped, or equip it otherwise.
+fn toggle_equipment(world: &World, player: Entity, what: Entity) {
+    if world.read_storage::<Equipped>().contains(what) {
+        world
+            .write_storage()
+            .insert(player, WantsToUnequip { what })
+            .unwrap();
+    } else {
+        world
+            .write_storage()
+            .insert(player, WantsToEquip { what })
+            .unwrap();
+    }
+    ActsOnTurns::spend(&mut world.write_storage(), player, Action::Equip);
+}
@@ -0,0 +1,76 @@
+use crate::{
+    graphics::{console::Console, renderer::ConsoleTileMap},
+    states::{GameState, GameStateEvent, GameTrans},
+    systems::{ActionBinding, LEVEL_UP_DEFENSE, LEVEL_UP_HP, LEVEL_UP_POWER},
+};
+
+use amethyst::{
+    ecs::Entity,
+    input::{is_close_requested, InputEvent},
+    prelude::*,
+    renderer::palette::Srgba,
+};
+
+/// Game state shown when the player reaches a new experience level.
+pub struct LevelUpState {
+    console: Entity,
+    level: u32,
+}
+
+impl LevelUpState {
+    pub fn new(console: Entity, level: u32) -> LevelUpState {
+        LevelUpState { console, level }
+    }
+}
+
+impl GameState for LevelUpState {
+    fn update(&mut self, StateData { world, .. }: &mut StateData<'_, GameData>) -> GameTrans {
+        let lines = [
+            format!("You have reached level {}!", self.level),
+            String::new(),
+            format!("Max HP  +{}", LEVEL_UP_HP),
+            format!("Power   +{}", LEVEL_UP_POWER),
+            format!("Defense +{}", LEVEL_UP_DEFENSE),
+            String::new(),
+            String::from("Your wounds are healed."),
+        ];
+        let (x, y, w, h) = (20, 12, 40, lines.len() as u32 + 4);
+
+        let title_col = Srgba::new(1., 1., 0., 1.);
+        let text_col = Srgba::new(1., 1., 1., 1.);
+
+        if let Some(con) = world
+            .write_storage::<ConsoleTileMap>()
+            .get_mut(self.console)
+        {
+            con.draw_box((x, y, w, h));
+
+            con.print_color((x + 2, y), " Level Up ", title_col);
+            con.print_color((x + 2, y + h - 1), " Press ENTER to continue ", title_col);
+
+            for (i, line) in lines.iter().enumerate() {
+                con.print_color((x + 2, y + i as u32 + 2), line, text_col);
+            }
+        }
+
+        Trans::None
+    }
+
+    fn on_stop(&mut self, StateData { world, .. }: StateData<'_, GameData>) {
+        if let Some(con) = world
+            .write_storage::<ConsoleTileMap>()
+            .get_mut(self.console)
+        {
+            con.clear();
+        }
+    }
+
+    fn handle_event(&mut self, _: StateData<'_, GameData>, event: GameStateEvent) -> GameTrans {
+        match &event {
+            StateEvent::Window(event) if is_close_requested(&event) => Trans::Quit,
+            StateEvent::Input(InputEvent::ActionPressed(ActionBinding::Confirm))
+            | StateEvent::Input(InputEvent::ActionPressed(ActionBinding::Cancel)) => Trans::Pop,
+            _ => Trans::None,
+        }
+    }
+}
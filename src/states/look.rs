@@ -0,0 +1,200 @@
+use crate::{
+    components::*,
+    core::map::WorldMap,
+    graphics::{
+        console::Console,
+        renderer::{self, ConsoleTileMap},
+    },
+    math::Point,
+    states::{GameState, GameStateEvent, GameTrans},
+    systems::ActionBinding,
+};
+
+use amethyst::{
+    ecs::{Entity, Join},
+    input::{is_close_requested, InputEvent},
+    prelude::*,
+    renderer::palette::Srgba,
+    tiles::Map,
+};
+
+/// Width of the box showing what is under the cursor.
+const BOX_WIDTH: u32 = 36;
+
+/// Game state used to examine the map with a movable cursor.
+///
+/// The terrain under the cursor is described, along with all the entities in view there.
+pub struct LookState {
+    console: Entity,
+    cursor: Point,
+    lines: Vec<(String, Srgba)>,
+}
+
+impl LookState {
+    pub fn new(cursor: Point, console: Entity) -> LookState {
+        LookState {
+            console,
+            cursor,
+            lines: Vec::new(),
+        }
+    }
+
+    // Moves the cursor to the given point and describes what lies there.
+    fn move_cursor(&mut self, world: &World, to: Point) {
+        let map = world.read_resource::<WorldMap>();
+        if !map.contains(to) {
+            return;
+        }
+
+        let title_col = Srgba::new(1., 1., 0., 1.);
+        let text_col = Srgba::new(1., 1., 1., 1.);
+        let info_col = Srgba::new(0.6, 0.6, 0.6, 1.);
+
+        self.cursor = to;
+        self.lines.clear();
+
+        let tile = map[to];
+        if !tile.revealed {
+            self.lines
+                .push((String::from("You don't know what is there."), info_col));
+            return;
+        }
+
+        let seen = if tile.visible {
+            "in view"
+        } else {
+            "remembered"
+        };
+        self.lines
+            .push((format!("{} ({})", tile.kind.name(), seen), text_col));
+
+        // Entities are only known while in view
+        if !tile.visible {
+            return;
+        }
+
+        let positions = world.read_storage::<Position>();
+        let names = world.read_storage::<Name>();
+        let stats = world.read_storage::<CombatStats>();
+        let brains = world.read_storage::<Brain>();
+        let descriptions = world.read_storage::<Description>();
+
+        for (e, _, Name(name)) in (&world.entities(), &positions, &names)
+            .join()
+            .filter(|(_, &Position(p), _)| p == to)
+        {
+            self.lines.push((String::new(), text_col));
+            self.lines.push((name.clone(), title_col));
+
+            if let Some(stats) = stats.get(e) {
+                self.lines
+                    .push((format!("HP: {}/{}", stats.hp, stats.max_hp), text_col));
+            }
+            if let Some(brain) = brains.get(e) {
+                self.lines
+                    .push((format!("It is {}.", brain.state.name()), text_col));
+            }
+            if let Some(Description(text)) = descriptions.get(e) {
+                self.lines.extend(
+                    wrap(text, BOX_WIDTH as usize - 4)
+                        .into_iter()
+                        .map(|line| (line, info_col)),
+                );
+            }
+        }
+    }
+}
+
+impl GameState for LookState {
+    fn on_start(&mut self, StateData { world, .. }: StateData<'_, GameData>) {
+        self.move_cursor(world, self.cursor);
+    }
+
+    fn update(&mut self, StateData { world, .. }: &mut StateData<'_, GameData>) -> GameTrans {
+        let center = *world.fetch::<Point>();
+        let cursor_col = Srgba::new(1., 1., 0., 1.);
+
+        if let Some(con) = world
+            .write_storage::<ConsoleTileMap>()
+            .get_mut(self.console)
+        {
+            let cell = renderer::map_to_console(con, center, self.cursor);
+            if let Some(cell) = cell {
+                con.put(cell, 'X', cursor_col);
+            }
+
+            // Keep the box on the opposite side of the cursor, so that it does not cover it
+            let half = con.dimensions()[0] / 2;
+            let x = match cell {
+                Some(cell) if cell.x() < half => half + 2,
+                _ => 2,
+            };
+            let (y, h) = (3, self.lines.len() as u32 + 4);
+
+            con.draw_box((x, y, BOX_WIDTH, h));
+            con.print_color((x + 2, y), " Look ", cursor_col);
+            con.print_color((x + 2, y + h - 1), " Press ESC to exit ", cursor_col);
+
+            for (i, (line, color)) in self.lines.iter().enumerate() {
+                con.print_color((x + 2, y + i as u32 + 2), line, *color);
+            }
+        }
+
+        Trans::None
+    }
+
+    fn on_stop(&mut self, StateData { world, .. }: StateData<'_, GameData>) {
+        if let Some(con) = world
+            .write_storage::<ConsoleTileMap>()
+            .get_mut(self.console)
+        {
+            con.clear();
+        }
+    }
+
+    fn handle_event(
+        &mut self,
+        StateData { world, .. }: StateData<'_, GameData>,
+        event: GameStateEvent,
+    ) -> GameTrans {
+        match &event {
+            StateEvent::Window(event) if is_close_requested(&event) => Trans::Quit,
+            StateEvent::Input(InputEvent::ActionPressed(action)) => match action {
+                ActionBinding::Move(dir) => {
+                    let (dx, dy) = dir.delta();
+                    let (x, y) = (self.cursor.x() as i32 + dx, self.cursor.y() as i32 + dy);
+                    if x >= 0 && y >= 0 {
+                        self.move_cursor(world, Point::new(x as u32, y as u32));
+                    }
+                    Trans::None
+                }
+                ActionBinding::Look | ActionBinding::Confirm | ActionBinding::Cancel => Trans::Pop,
+                _ => Trans::None,
+            },
+            _ => Trans::None,
+        }
+    }
+}
+
+// Splits a text into lines no longer than `width` characters, breaking them between words.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + word.chars().count() + 1 > width {
+            lines.push(line);
+            line = String::new();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
@@ -0,0 +1,301 @@
+use crate::{
+    core::save,
+    graphics::{
+        console::Console,
+        renderer::{self, ConsoleTileMap, CONSOLE_WIDTH},
+    },
+    resources::{MenuNotice, Settings, TileDimension},
+    states::{GameState, GameStateEvent, GameStateWrapper, GameTrans, RunState},
+    systems::ActionBinding,
+};
+
+use amethyst::{
+    assets::Handle,
+    core::transform::Transform,
+    ecs::Entity,
+    input::{is_close_requested, InputEvent},
+    prelude::*,
+    renderer::{palette::Srgba, SpriteSheet},
+};
+
+/// Maximum number of digits of a seed typed in by hand.
+const MAX_SEED_DIGITS: usize = 20;
+
+/// Width of the menu box.
+const BOX_WIDTH: u32 = 40;
+
+/// Screens the main menu can show.
+enum Screen {
+    Main,
+    SeedEntry(String),
+    Settings,
+}
+
+/// Game state shown when the game starts, and every time a run is over.
+///
+/// The menu has its own camera and console, which only exist while the menu is the active
+/// state: the running game tears down the whole world when it stops.
+pub struct MainMenuState {
+    seed: Option<u64>,
+    screen: Screen,
+    can_continue: bool,
+    notice: Option<String>,
+    sheet: Option<Handle<SpriteSheet>>,
+    view: Vec<Entity>,
+    console: Option<Entity>,
+}
+
+impl MainMenuState {
+    /// Creates the main menu. If a seed is given, a new game is started from it right away.
+    pub fn new(seed: Option<u64>) -> MainMenuState {
+        MainMenuState {
+            seed,
+            screen: Screen::Main,
+            can_continue: false,
+            notice: None,
+            sheet: None,
+            view: Vec::new(),
+            console: None,
+        }
+    }
+
+    // Creates the camera and the console used to draw the menu.
+    fn create_view(&mut self, world: &mut World) {
+        let sheet = match &self.sheet {
+            Some(sheet) => sheet.clone(),
+            None => return,
+        };
+
+        let pivot = world.create_entity().with(Transform::default()).build();
+        let camera = renderer::spawn_camera(world, pivot);
+        let console = renderer::create_console(world, pivot, sheet);
+
+        self.view = vec![pivot, camera, console];
+        self.console = Some(console);
+        self.screen = Screen::Main;
+        self.can_continue = save::save_path().map(|path| path.exists()).unwrap_or(false);
+
+        // Whatever went wrong in the last run is shown until the menu is left again
+        self.notice = world.write_resource::<MenuNotice>().0.take();
+    }
+
+    // Deletes the camera and the console, so that they do not overlap the running game.
+    fn delete_view(&mut self, world: &mut World) {
+        world.delete_entities(&self.view).ok();
+        self.view.clear();
+        self.console = None;
+    }
+
+    // Lists the options of the current screen, along with the keys selecting them.
+    fn options(&self, world: &World) -> Vec<(char, String)> {
+        match self.screen {
+            Screen::Main => {
+                let mut options = vec![('n', "New game"), ('s', "New game with seed")];
+                if self.can_continue {
+                    options.push(('c', "Continue"));
+                }
+                options.push(('o', "Settings"));
+                options.push(('q', "Quit"));
+
+                options
+                    .into_iter()
+                    .map(|(key, text)| (key, String::from(text)))
+                    .collect()
+            }
+            Screen::Settings => {
+                let on_off = |flag| if flag { "on" } else { "off" };
+                let settings = world.read_resource::<Settings>();
+
+                vec![
+                    ('f', format!("Show FPS: {}", on_off(settings.show_fps))),
+                    ('b', String::from("Back")),
+                ]
+            }
+            Screen::SeedEntry(_) => Vec::new(),
+        }
+    }
+
+    // Handles a key typed while entering a seed.
+    fn type_seed(&mut self, key: char) {
+        if let Screen::SeedEntry(seed) = &mut self.screen {
+            match key {
+                '0'..='9' if seed.len() < MAX_SEED_DIGITS => seed.push(key),
+                '\u{8}' => {
+                    seed.pop();
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // Handles a key typed on the current screen.
+    fn select(&mut self, world: &mut World, key: char) -> GameTrans {
+        match (&self.screen, key) {
+            (Screen::Main, 'n') => new_game(None),
+            (Screen::Main, 's') => {
+                self.screen = Screen::SeedEntry(String::new());
+                Trans::None
+            }
+            (Screen::Main, 'c') if self.can_continue => match save::save_path() {
+                Ok(path) => Trans::Push(Box::new(GameStateWrapper::new(RunState::from_save(path)))),
+                Err(_) => Trans::None,
+            },
+            (Screen::Main, 'o') => {
+                self.screen = Screen::Settings;
+                Trans::None
+            }
+            (Screen::Main, 'q') => Trans::Quit,
+            (Screen::Settings, 'f') => {
+                let mut settings = world.write_resource::<Settings>();
+                settings.show_fps = !settings.show_fps;
+                if let Err(e) = save::save_settings(&settings) {
+                    amethyst::log::warn!("Could not save the settings: {}", e);
+                }
+                Trans::None
+            }
+            (Screen::Settings, 'b') => {
+                self.screen = Screen::Main;
+                Trans::None
+            }
+            (Screen::SeedEntry(_), _) => {
+                self.type_seed(key);
+                Trans::None
+            }
+            _ => Trans::None,
+        }
+    }
+
+    // Confirms the seed being entered, if any, starting a new game from it.
+    fn confirm(&self) -> GameTrans {
+        match &self.screen {
+            Screen::SeedEntry(seed) if seed.is_empty() => new_game(None),
+            Screen::SeedEntry(seed) => match seed.parse::<u64>() {
+                Ok(seed) => new_game(Some(seed)),
+                // Twenty digits can still overflow, so just wait for a fix
+                Err(_) => Trans::None,
+            },
+            _ => Trans::None,
+        }
+    }
+}
+
+impl GameState for MainMenuState {
+    fn on_start(&mut self, StateData { world, .. }: StateData<'_, GameData>) {
+        world.insert(TileDimension(20));
+        world.insert(save::load_settings());
+        world.insert(MenuNotice::default());
+
+        self.sheet = Some(renderer::load_sprite_sheet(
+            world,
+            "texture/cp437_20x20.png",
+            "texture/cp437_20x20.ron",
+        ));
+
+        self.create_view(world);
+    }
+
+    fn on_pause(&mut self, StateData { world, .. }: StateData<'_, GameData>) {
+        self.delete_view(world);
+    }
+
+    fn on_resume(&mut self, StateData { world, .. }: StateData<'_, GameData>) {
+        self.create_view(world);
+    }
+
+    fn on_stop(&mut self, StateData { world, .. }: StateData<'_, GameData>) {
+        self.delete_view(world);
+    }
+
+    fn update(&mut self, StateData { world, .. }: &mut StateData<'_, GameData>) -> GameTrans {
+        // A seed given on the command line skips the menu altogether
+        if let Some(seed) = self.seed.take() {
+            return new_game(Some(seed));
+        }
+
+        let title_col = Srgba::new(1., 1., 0., 1.);
+        let text_col = Srgba::new(1., 1., 1., 1.);
+        let key_col = Srgba::new(1., 1., 0., 1.);
+        let error_col = Srgba::new(1., 0., 0., 1.);
+
+        let options = self.options(world);
+
+        let (x, y, w) = ((CONSOLE_WIDTH - BOX_WIDTH) / 2, 16, BOX_WIDTH);
+        let h = match self.screen {
+            Screen::SeedEntry(_) => 7,
+            _ => options.len() as u32 + 4,
+        };
+
+        let console = match self.console {
+            Some(console) => console,
+            None => return Trans::None,
+        };
+
+        if let Some(con) = world.write_storage::<ConsoleTileMap>().get_mut(console) {
+            con.clear();
+
+            let title = "M I S T E R Y";
+            let title_x = (CONSOLE_WIDTH - title.len() as u32) / 2;
+            con.print_color((title_x, y - 4), title, title_col);
+
+            con.draw_box((x, y, w, h));
+
+            match &self.screen {
+                Screen::Main => con.print_color((x + 2, y), " Main Menu ", title_col),
+                Screen::Settings => con.print_color((x + 2, y), " Settings ", title_col),
+                Screen::SeedEntry(seed) => {
+                    con.print_color((x + 2, y), " New Game ", title_col);
+                    con.print((x + 2, y + 2), "Seed:");
+                    con.print_color((x + 8, y + 2), format!("{}_", seed), key_col);
+                    con.print((x + 2, y + 4), "Leave empty for a random one.");
+                    con.print_color(
+                        (x + 2, y + h - 1),
+                        " ENTER to start, ESC to go back ",
+                        title_col,
+                    );
+                }
+            }
+
+            for (i, (key, text)) in options.iter().enumerate() {
+                let y = y + 2 + i as u32;
+                con.put((x + 2, y), '(', text_col);
+                con.put((x + 3, y), *key, key_col);
+                con.put((x + 4, y), ')', text_col);
+                con.print((x + 6, y), text);
+            }
+
+            if let Some(notice) = &self.notice {
+                let notice: String = notice.chars().take(CONSOLE_WIDTH as usize).collect();
+                let notice_x = (CONSOLE_WIDTH - notice.chars().count() as u32) / 2;
+                con.print_color((notice_x, y + h + 2), notice, error_col);
+            }
+        }
+
+        Trans::None
+    }
+
+    fn handle_event(
+        &mut self,
+        StateData { world, .. }: StateData<'_, GameData>,
+        event: GameStateEvent,
+    ) -> GameTrans {
+        match &event {
+            StateEvent::Window(event) if is_close_requested(&event) => Trans::Quit,
+            StateEvent::Input(InputEvent::ActionPressed(ActionBinding::Confirm)) => self.confirm(),
+            StateEvent::Input(InputEvent::ActionPressed(ActionBinding::Cancel)) => {
+                self.screen = Screen::Main;
+                Trans::None
+            }
+            StateEvent::Input(InputEvent::KeyTyped(key)) => self.select(world, *key),
+            _ => Trans::None,
+        }
+    }
+}
+
+// Starts a new run, from the given seed or from a random one.
+fn new_game(seed: Option<u64>) -> GameTrans {
+    let state = match seed {
+        Some(seed) => RunState::from_seed(seed),
+        None => RunState::default(),
+    };
+    Trans::Push(Box::new(GameStateWrapper::new(state)))
+}
@@ -1,11 +1,23 @@
 //! This modules contains all the Amethyst [`State`]s that the game can be in.
 
 mod game;
+mod game_over;
 mod inventory;
+mod level_up;
+mod look;
+mod main_menu;
+mod targeting;
+mod travel;
 
 // Re-export all modules
 pub use game::*;
+pub use game_over::*;
 pub use inventory::*;
+pub use level_up::*;
+pub use look::*;
+pub use main_menu::*;
+pub use targeting::*;
+pub use travel::*;
 
 use crate::systems::GameBindings;
 
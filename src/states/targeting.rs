@@ -0,0 +1,139 @@
+use crate::{
+    components::*,
+    graphics::{
+        console::Console,
+        renderer::{self, ConsoleTileMap},
+    },
+    math::{self, Point},
+    states::{GameState, GameStateEvent, GameTrans},
+    systems::ActionBinding,
+};
+
+use amethyst::{
+    ecs::{Entity, Join},
+    input::{is_close_requested, InputEvent},
+    prelude::*,
+    renderer::palette::Srgba,
+};
+use std::collections::HashSet;
+
+/// Game state used to select the target location of a ranged item.
+pub struct TargetingState {
+    item: Entity,
+    range: u32,
+    console: Entity,
+    cursor: Point,
+    targets: HashSet<Point>,
+}
+
+impl TargetingState {
+    pub fn new(item: Entity, range: u32, console: Entity) -> TargetingState {
+        TargetingState {
+            item,
+            range,
+            console,
+            cursor: Point::default(),
+            targets: HashSet::new(),
+        }
+    }
+}
+
+impl GameState for TargetingState {
+    fn on_start(&mut self, StateData { world, .. }: StateData<'_, GameData>) {
+        let players = world.read_storage::<Player>();
+        let positions = world.read_storage::<Position>();
+        let viewsheds = world.read_storage::<Viewshed>();
+
+        // Only the tiles that the player can see and that are within range can be targeted
+        if let Some((_, &Position(p), vs)) = (&players, &positions, &viewsheds).join().next() {
+            self.cursor = p;
+            self.targets = vs
+                .visible
+                .iter()
+                .filter(|&&pt| math::distance_2d(p, pt) <= self.range)
+                .copied()
+                .collect();
+        }
+    }
+
+    fn update(&mut self, StateData { world, .. }: &mut StateData<'_, GameData>) -> GameTrans {
+        let center = *world.fetch::<Point>();
+
+        let range_col = Srgba::new(0.0, 0.3, 0.6, 1.);
+        let cursor_col = Srgba::new(1., 1., 0., 1.);
+
+        if let Some(con) = world
+            .write_storage::<ConsoleTileMap>()
+            .get_mut(self.console)
+        {
+            for pt in &self.targets {
+                if let Some(cell) = renderer::map_to_console(con, center, *pt) {
+                    con.tint(cell, range_col);
+                }
+            }
+
+            if let Some(cell) = renderer::map_to_console(con, center, self.cursor) {
+                con.put(cell, 'X', cursor_col);
+            }
+
+            con.print_color(
+                (2, 1),
+                " Select a target: ENTER to confirm, ESC to cancel ",
+                cursor_col,
+            );
+        }
+
+        Trans::None
+    }
+
+    fn on_stop(&mut self, StateData { world, .. }: StateData<'_, GameData>) {
+        if let Some(con) = world
+            .write_storage::<ConsoleTileMap>()
+            .get_mut(self.console)
+        {
+            con.clear();
+        }
+    }
+
+    fn handle_event(
+        &mut self,
+        StateData { world, .. }: StateData<'_, GameData>,
+        event: GameStateEvent,
+    ) -> GameTrans {
+        match &event {
+            StateEvent::Window(event) if is_close_requested(&event) => Trans::Quit,
+            StateEvent::Input(InputEvent::ActionPressed(action)) => match action {
+                ActionBinding::Move(dir) => {
+                    let next = self.cursor + dir.delta();
+                    if self.targets.contains(&next) {
+                        self.cursor = next;
+                    }
+                    Trans::None
+                }
+                ActionBinding::Confirm => {
+                    if let Some((player, _)) = (&world.entities(), &world.read_storage::<Player>())
+                        .join()
+                        .next()
+                    {
+                        world
+                            .write_storage()
+                            .insert(
+                                player,
+                                WantsToUseItem {
+                                    what: self.item,
+                                    target: Some(self.cursor),
+                                },
+                            )
+                            .unwrap();
+
+                        ActsOnTurns::spend(&mut world.write_storage(), player, Action::UseItem);
+                    }
+                    Trans::Pop
+                }
+                ActionBinding::Cancel => Trans::Pop,
+                _ => Trans::None,
+            },
+            _ => Trans::None,
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{
+    components::*,
+    core::map::{self, WorldMap},
+    graphics::{
+        console::Console,
+        renderer::{self, ConsoleTileMap},
+    },
+    math::Point,
+    resources::CombatLog,
+    states::{GameState, GameStateEvent, GameTrans},
+    systems::ActionBinding,
+};
+
+use amethyst::{
+    ecs::{Entity, Join},
+    input::{is_close_requested, InputEvent},
+    prelude::*,
+    renderer::palette::Srgba,
+};
+
+/// Game state used to pick a destination for the player to travel to.
+///
+/// The path to the destination is previewed until the player confirms it,
+/// either with the confirm key or by clicking the destination again.
+pub struct TravelState {
+    console: Entity,
+    cursor: Point,
+    path: Option<Vec<Point>>,
+}
+
+impl TravelState {
+    pub fn new(cursor: Point, console: Entity) -> TravelState {
+        TravelState {
+            console,
+            cursor,
+            path: None,
+        }
+    }
+
+    // Moves the cursor to the given point and computes the path leading there.
+    // Only paths through tiles which have already been revealed are allowed.
+    fn move_cursor(&mut self, world: &World, to: Point) {
+        let map = world.read_resource::<WorldMap>();
+        if !map.contains(to) {
+            return;
+        }
+
+        let start = (
+            &world.read_storage::<Player>(),
+            &world.read_storage::<Position>(),
+        )
+            .join()
+            .map(|(_, &Position(p))| p)
+            .next();
+
+        self.cursor = to;
+        self.path = start
+            .and_then(|start| map::a_star_search(&*map, start, to, true))
+            .filter(|path| path.iter().all(|&p| map[p].revealed));
+    }
+
+    // Sets the player on its way to the cursor, if there is a path leading there.
+    fn travel(&self, world: &mut World) -> GameTrans {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => {
+                world
+                    .write_resource::<CombatLog>()
+                    .push("You don't know the way there.");
+                return Trans::Pop;
+            }
+        };
+
+        let player = (
+            &world.entities(),
+            &world.read_storage::<Player>(),
+            &world.read_storage::<CombatStats>(),
+        )
+            .join()
+            .map(|(e, _, stats)| (e, stats.hp))
+            .next();
+
+        if let Some((player, hp)) = player {
+            world
+                .write_storage()
+                .insert(
+                    player,
+                    AutoMove {
+                        goal: AutoMoveGoal::Travel(path[1..].to_vec()),
+                        hp,
+                        from: path[0],
+                    },
+                )
+                .unwrap();
+        }
+
+        Trans::Pop
+    }
+}
+
+impl GameState for TravelState {
+    fn on_start(&mut self, StateData { world, .. }: StateData<'_, GameData>) {
+        self.move_cursor(world, self.cursor);
+    }
+
+    fn update(&mut self, StateData { world, .. }: &mut StateData<'_, GameData>) -> GameTrans {
+        let center = *world.fetch::<Point>();
+
+        let path_col = Srgba::new(0.0, 0.3, 0.6, 1.);
+        let cursor_col = Srgba::new(1., 1., 0., 1.);
+
+        if let Some(con) = world
+            .write_storage::<ConsoleTileMap>()
+            .get_mut(self.console)
+        {
+            for pt in self.path.iter().flatten() {
+                if let Some(cell) = renderer::map_to_console(con, center, *pt) {
+                    con.tint(cell, path_col);
+                }
+            }
+
+            if let Some(cell) = renderer::map_to_console(con, center, self.cursor) {
+                con.put(cell, 'X', cursor_col);
+            }
+
+            con.print_color(
+                (2, 1),
+                " Select a destination: ENTER to travel, ESC to cancel ",
+                cursor_col,
+            );
+        }
+
+        Trans::None
+    }
+
+    fn on_stop(&mut self, StateData { world, .. }: StateData<'_, GameData>) {
+        if let Some(con) = world
+            .write_storage::<ConsoleTileMap>()
+            .get_mut(self.console)
+        {
+            con.clear();
+        }
+    }
+
+    fn handle_event(
+        &mut self,
+        StateData { world, .. }: StateData<'_, GameData>,
+        event: GameStateEvent,
+    ) -> GameTrans {
+        match &event {
+            StateEvent::Window(event) if is_close_requested(&event) => Trans::Quit,
+            StateEvent::Input(InputEvent::ActionPressed(action)) => match action {
+                ActionBinding::Move(dir) => {
+                    let (dx, dy) = dir.delta();
+                    let (x, y) = (self.cursor.x() as i32 + dx, self.cursor.y() as i32 + dy);
+                    if x >= 0 && y >= 0 {
+                        self.move_cursor(world, Point::new(x as u32, y as u32));
+                    }
+                    Trans::None
+                }
+                ActionBinding::Click => match renderer::mouse_to_map(world, self.console) {
+                    Some(pt) if pt == self.cursor => self.travel(world),
+                    Some(pt) => {
+                        self.move_cursor(world, pt);
+                        Trans::None
+                    }
+                    None => Trans::None,
+                },
+                ActionBinding::Confirm => self.travel(world),
+                ActionBinding::Cancel => Trans::Pop,
+                _ => Trans::None,
+            },
+            _ => Trans::None,
+        }
+    }
+}
@@ -2,21 +2,47 @@
 
 use crate::{
     components::*,
-    core::map::{self, WorldMap},
-    math,
+    core::map::{DijkstraMap, TileKind, WorldMap},
+    math::{self, Point},
+    resources::{CombatLog, Depth, SeededRng},
 };
 
 use amethyst::{
     derive::SystemDesc,
-    ecs::{Entities, Join, Read, ReadStorage, System, SystemData, WriteStorage},
+    ecs::{Entities, Join, Read, ReadStorage, System, SystemData, Write, WriteStorage},
 };
+use rand::{
+    seq::{IteratorRandom, SliceRandom},
+    RngCore,
+};
+use std::collections::HashMap;
+
+/// Number of turns a monster keeps looking for a hostile it lost sight of.
+pub const SEARCH_TURNS: u32 = 10;
+
+/// Distance at which sleeping monsters notice a hostile.
+pub const WAKE_DISTANCE: u32 = 2;
+
+/// Number of spots of each level that wandering monsters roam between.
+pub const WANDER_SPOTS: usize = 8;
 
 /// Monster logic processing.
 ///
-/// For each monster in the field, the system checks if any player unit is in its FoV
-/// and either chases it, or if it is in an adjacent tiles, tries to attack.
-#[derive(SystemDesc)]
-pub struct MonsterAI;
+/// Each monster goes through the states of its [`Brain`] as the situation changes:
+/// sleeping monsters wake up when hurt or when a hostile gets close, then wander around until
+/// one comes into view. Hostiles in view are chased and attacked once in reach, unless the
+/// monster is hurt badly enough to run away. Hostiles who step out of view are looked for
+/// where they were last seen, until the monster gives up and goes back to wandering.
+///
+/// Monsters heading to the same spot share a [`DijkstraMap`] leading to it. To make the most
+/// of this, wandering monsters only roam between a few spots, picked once for each level.
+/// Confused monsters forget about all of this, and just stumble around.
+#[derive(Default, SystemDesc)]
+#[system_desc(name(MonsterAIDesc))]
+pub struct MonsterAI {
+    #[system_desc(skip)]
+    wander_spots: Option<(u32, Vec<Point>)>,
+}
 
 impl<'s> System<'s> for MonsterAI {
     #[allow(clippy::type_complexity)]
@@ -26,10 +52,16 @@ impl<'s> System<'s> for MonsterAI {
         ReadStorage<'s, Faction>,
         ReadStorage<'s, Position>,
         ReadStorage<'s, Viewshed>,
+        ReadStorage<'s, OpensDoors>,
+        ReadStorage<'s, StatusEffects>,
+        ReadStorage<'s, CombatStats>,
+        WriteStorage<'s, Brain>,
         WriteStorage<'s, ActsOnTurns>,
         WriteStorage<'s, WantsToMove>,
         WriteStorage<'s, TargetedForMelee>,
         Read<'s, WorldMap>,
+        Read<'s, Depth>,
+        Write<'s, SeededRng>,
     );
 
     fn run(
@@ -40,46 +72,303 @@ impl<'s> System<'s> for MonsterAI {
             factions,
             positions,
             viewsheds,
+            door_openers,
+            statuses,
+            combat_stats,
+            mut brains,
             mut actors,
             mut movers,
             mut melee_targets,
             map,
+            depth,
+            mut rng,
         ): Self::SystemData,
     ) {
+        // Every level has its own spots to wander to, all reachable from its stairs
+        // and safe to stand on
+        let Depth(depth) = *depth;
+        if self.wander_spots.as_ref().map(|&(d, _)| d) != Some(depth) {
+            let spots = map
+                .find(TileKind::DownStairs)
+                .map(|stairs| map.reachable_from(stairs))
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|&p| map[p].kind.is_spawnable())
+                .choose_multiple(&mut *rng, WANDER_SPOTS);
+            self.wander_spots = Some((depth, spots));
+        }
+        let spots = self
+            .wander_spots
+            .as_ref()
+            .map(|(_, spots)| &spots[..])
+            .unwrap_or(&[]);
+
         let attackers = (
             &entities,
             &mut actors,
+            &mut brains,
             &factions,
             &viewsheds,
             &positions,
+            &combat_stats,
             !&players,
         );
 
         let targets = (&entities, &factions, &positions);
 
-        for (attacker, actor, &Faction(f1), vs, &Position(p1), _) in attackers.join() {
-            if !actor.perform() {
+        // Distance maps are computed lazily, once per goal and door opening ability
+        let mut chase_maps = HashMap::new();
+
+        for (attacker, actor, brain, &Faction(f1), vs, &Position(p1), stats, _) in attackers.join()
+        {
+            if !actor.can_act() {
+                continue;
+            }
+
+            let hurt = brain.was_hurt(stats.hp);
+
+            if statuses
+                .get(attacker)
+                .map(|s| s.is_confused())
+                .unwrap_or(false)
+            {
+                // Where it stumbles to is up to the move resolver
+                movers.insert(attacker, WantsToMove { to: p1 }).unwrap();
+                actor.perform(Action::Move);
                 continue;
             }
 
-            for (target, &Faction(f2), &Position(p2)) in targets.join() {
-                // Skip not visibible and allies
-                if f1 == f2 || !vs.visible.contains(&p2) {
+            // Only the closest hostile in view is worth any attention
+            let hostile = targets
+                .join()
+                .filter(|&(_, &Faction(f2), &Position(p2))| f1 != f2 && vs.visible.contains(&p2))
+                .min_by_key(|&(_, _, &Position(p2))| math::distance_2d(p1, p2))
+                .map(|(target, _, &Position(p2))| (target, p2));
+
+            let seen = hostile.map(|(_, p2)| p2);
+            brain.state = next_state(brain, stats, p1, seen, hurt, spots, &mut *rng);
+
+            let opens_doors = door_openers.contains(attacker);
+
+            let step = match (brain.state, hostile) {
+                (AiState::Hunting(p2), Some((target, _))) if math::distance_2d(p1, p2) == 1 => {
+                    TargetedForMelee::target(&mut melee_targets, attacker, target);
+                    actor.perform(Action::Attack);
                     continue;
                 }
+                (AiState::Fleeing, Some((target, p2))) => {
+                    let step = chase_map(&mut chase_maps, &*map, p2, opens_doors).uphill(&*map, p1);
 
-                // If in range, target for combat, otherwise move closer.
-                if math::distance_2d(p1, p2) == 1 {
-                    TargetedForMelee::target(&mut melee_targets, attacker, target);
-                } else if let Some(path) = map::a_star_search(&*map, p1, p2) {
-                    movers
-                        .insert(attacker, WantsToMove { to: path[1] })
-                        .unwrap();
+                    // Cornered monsters fight back as a last resort
+                    if step.is_none() && math::distance_2d(p1, p2) == 1 {
+                        TargetedForMelee::target(&mut melee_targets, attacker, target);
+                        actor.perform(Action::Attack);
+                        continue;
+                    }
+                    step
+                }
+                _ => brain.state.goal().and_then(|goal| {
+                    chase_map(&mut chase_maps, &*map, goal, opens_doors).downhill(&*map, p1)
+                }),
+            };
+
+            match step {
+                Some(to) => {
+                    movers.insert(attacker, WantsToMove { to }).unwrap();
+                    actor.perform(Action::Move);
+                }
+                None => {
+                    // A destination which cannot be reached is not worth waiting for
+                    if let AiState::Wandering(_) | AiState::Searching { .. } = brain.state {
+                        brain.state = AiState::Wandering(None);
+                    }
+                    actor.perform(Action::Wait);
                 }
+            }
+        }
+    }
+}
+
+// Returns the distance map leading to the given goal, computing it only once.
+fn chase_map<'a>(
+    maps: &'a mut HashMap<(Point, bool), DijkstraMap>,
+    map: &WorldMap,
+    goal: Point,
+    opens_doors: bool,
+) -> &'a DijkstraMap {
+    maps.entry((goal, opens_doors))
+        .or_insert_with(|| DijkstraMap::new(map, &[goal], opens_doors))
+}
 
-                // Don't chase multiple units!
-                break;
+// Decides what a monster should be up to, given what it sees and whether it has just been hurt.
+fn next_state(
+    brain: &Brain,
+    stats: &CombatStats,
+    here: Point,
+    hostile: Option<Point>,
+    hurt: bool,
+    spots: &[Point],
+    rng: &mut dyn RngCore,
+) -> AiState {
+    match (brain.state, hostile) {
+        (AiState::Sleeping, Some(p2)) if !hurt && math::distance_2d(here, p2) > WAKE_DISTANCE => {
+            AiState::Sleeping
+        }
+        (AiState::Sleeping, None) if !hurt => AiState::Sleeping,
+        (_, Some(_)) if brain.should_flee(stats) => AiState::Fleeing,
+        (_, Some(p2)) => AiState::Hunting(p2),
+        (AiState::Hunting(last_seen), None) => AiState::Searching {
+            last_seen,
+            turns: SEARCH_TURNS,
+        },
+        (AiState::Searching { last_seen, turns }, None) if turns > 0 && last_seen != here => {
+            AiState::Searching {
+                last_seen,
+                turns: turns - 1,
             }
         }
+        (AiState::Wandering(Some(goal)), None) if goal != here => AiState::Wandering(Some(goal)),
+        // Waking up, giving up a search, losing any pursuer or getting somewhere
+        // all end up in the monster heading to a new random spot
+        _ => AiState::Wandering(spots.choose(rng).copied()),
     }
 }
+
+/// Moves entities on their own, one step per turn, as long as nothing worth attention happens.
+///
+/// Entities with an [`AutoMove`] component stop as soon as a hostile comes into view,
+/// they step on an item, they get hurt, or there is nowhere left to go.
+#[derive(SystemDesc)]
+pub struct AutoMoveSystem;
+
+impl<'s> System<'s> for AutoMoveSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'s>,
+        ReadStorage<'s, Faction>,
+        ReadStorage<'s, Name>,
+        ReadStorage<'s, Position>,
+        ReadStorage<'s, Pickable>,
+        ReadStorage<'s, CombatStats>,
+        ReadStorage<'s, Viewshed>,
+        ReadStorage<'s, OpensDoors>,
+        WriteStorage<'s, ActsOnTurns>,
+        WriteStorage<'s, AutoMove>,
+        WriteStorage<'s, WantsToMove>,
+        Read<'s, WorldMap>,
+        Write<'s, CombatLog>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            factions,
+            names,
+            positions,
+            pickables,
+            combat_stats,
+            viewsheds,
+            door_openers,
+            mut actors,
+            mut auto_moves,
+            mut movers,
+            map,
+            mut log,
+        ): Self::SystemData,
+    ) {
+        let mut stopped = Vec::new();
+
+        let walkers = (
+            &entities,
+            &mut actors,
+            &mut auto_moves,
+            &factions,
+            &positions,
+            &combat_stats,
+            &viewsheds,
+        );
+
+        for (e, actor, auto, &Faction(f1), &Position(p), stats, vs) in walkers.join() {
+            if !actor.can_act() {
+                continue;
+            }
+
+            let hostile = (&factions, &positions, &names, &combat_stats)
+                .join()
+                .find(|(&Faction(f2), &Position(p2), _, _)| f1 != f2 && vs.visible.contains(&p2))
+                .map(|(_, _, Name(name), _)| name);
+
+            // Items are only worth stopping for when stepping on them
+            let item = (&positions, &names, &pickables)
+                .join()
+                .find(|(&Position(p2), _, _)| p != auto.from && p == p2)
+                .map(|(_, Name(name), _)| name);
+
+            let next = match auto.goal {
+                AutoMoveGoal::Explore => explore_step(&*map, p, door_openers.contains(e)),
+                AutoMoveGoal::Travel(ref mut path) => travel_step(&*map, p, path),
+            };
+
+            let reason = if let Some(name) = hostile {
+                Some(format!("{} comes into view.", name))
+            } else if let Some(name) = item {
+                Some(format!("You find a {}.", name))
+            } else if stats.hp < auto.hp {
+                Some(String::from("You are hurt, and stop."))
+            } else if next.is_none() {
+                match auto.goal {
+                    AutoMoveGoal::Explore => Some("There is nothing left to explore."),
+                    AutoMoveGoal::Travel(ref path) if !path.is_empty() => {
+                        Some("Your path is blocked.")
+                    }
+                    AutoMoveGoal::Travel(_) => None, // arrived
+                }
+                .map(String::from)
+            } else {
+                None
+            };
+
+            match (reason, next) {
+                (None, Some(to)) => {
+                    actor.perform(Action::Move);
+                    movers.insert(e, WantsToMove { to }).unwrap();
+                    auto.hp = stats.hp;
+                    auto.from = p;
+                }
+                (reason, _) => {
+                    if let Some(reason) = reason {
+                        log.push(reason);
+                    }
+                    stopped.push(e);
+                }
+            }
+        }
+
+        for e in stopped {
+            auto_moves.remove(e);
+        }
+    }
+}
+
+// Returns the next step towards the closest tile that has not been revealed yet.
+fn explore_step(map: &WorldMap, from: Point, opens_doors: bool) -> Option<Point> {
+    let unexplored = (0..map.height())
+        .flat_map(|y| (0..map.width()).map(move |x| Point::new(x, y)))
+        .filter(|&p| !map[p].revealed && map[p].kind.is_passable())
+        .collect::<Vec<_>>();
+
+    DijkstraMap::new(map, &unexplored, opens_doors).downhill(map, from)
+}
+
+// Returns the next step along a path, dropping the points which have already been reached.
+// Steps onto tiles blocked by someone are not taken, while closed doors get opened on the way.
+fn travel_step(map: &WorldMap, from: Point, path: &mut Vec<Point>) -> Option<Point> {
+    if let Some(reached) = path.iter().position(|&p| p == from) {
+        path.drain(..=reached);
+    }
+
+    path.first()
+        .copied()
+        .filter(|&p| !map[p].blocked || map[p].kind == TileKind::ClosedDoor)
+}
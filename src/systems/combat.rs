@@ -1,59 +1,106 @@
 //! This module contains all the combat-related systems.
 
-use crate::{components::*, resources::CombatLog};
+use crate::{
+    components::*,
+    resources::{CombatLog, LevelUp, RunStats},
+};
 
 use amethyst::{
     derive::SystemDesc,
-    ecs::{Entities, Join, ReadStorage, System, SystemData, Write, WriteStorage},
+    ecs::{Entities, Entity, Join, ReadStorage, System, SystemData, Write, WriteStorage},
 };
+use serde::{Deserialize, Serialize};
+use std::{cmp::Reverse, collections::HashMap};
+
+/// Number of ticks in a turn, ie. the time an entity of normal speed takes to move once.
+pub const TICKS_PER_TURN: u64 = 10;
 
-/// Enum representing one of the possible turns in the state logic.
-#[derive(Copy, Clone, PartialEq)]
-pub enum Turn {
-    Player,
-    Others,
+/// Resource holding the time elapsed since the beginning of the run.
+#[derive(Default, Copy, Clone, Serialize, Deserialize)]
+pub struct GameClock {
+    pub ticks: u64,
 }
 
-impl Default for Turn {
-    fn default() -> Self {
-        Turn::Player
+impl GameClock {
+    /// Returns the number of whole turns elapsed so far.
+    pub fn turn(self) -> u64 {
+        self.ticks / TICKS_PER_TURN
     }
 }
 
-/// System that manages which entities get to act in the current turn.
+/// System that decides which entities get to act next.
 ///
-/// At each invokation, the system checks which turns it's currently on,
-/// and for each entity that should act on that turn, it checks if any of them
-/// still has any AP left. If so, the turn keeps going until all entities
-/// able to act have depleted their APs. Otherwise, the turn changes, and all
-/// the entities that can act on the new turn have their AP replenished.
-#[derive(Default, SystemDesc)]
-pub struct TurnSystem {
-    current: Turn,
-}
+/// As long as anyone still has to take their turn, the system just waits for them.
+/// Otherwise, it lets time pass until someone has gathered enough energy to act,
+/// and gives the turn to the readiest of them, the fastest one breaking any ties.
+/// Monsters ready before the player all act together, while the player always acts alone,
+/// waiting for input. Status effects such as haste and slow change how fast energy is gained.
+///
+/// The time elapsed is kept in the [`GameClock`] resource, so that it can be persisted.
+#[derive(SystemDesc)]
+pub struct TurnSystem;
 
 impl<'s> System<'s> for TurnSystem {
-    type SystemData = (WriteStorage<'s, ActsOnTurns>, ReadStorage<'s, Player>);
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'s>,
+        WriteStorage<'s, ActsOnTurns>,
+        ReadStorage<'s, Player>,
+        ReadStorage<'s, StatusEffects>,
+        Write<'s, GameClock>,
+        Write<'s, RunStats>,
+    );
 
-    fn run(&mut self, (mut actors, players): Self::SystemData) {
-        match self.current {
-            Turn::Player => {
-                if (&actors, &players).join().any(|(a, _)| a.can_act()) {
-                    return;
-                }
-                for (actor, _) in (&mut actors, !&players).join() {
-                    actor.refresh();
-                }
-                self.current = Turn::Others;
+    fn run(
+        &mut self,
+        (entities, mut actors, players, statuses, mut clock, mut stats): Self::SystemData,
+    ) {
+        if actors.join().any(|a| a.can_act()) {
+            return;
+        }
+
+        let speed_of =
+            |e: Entity, base: u32| statuses.get(e).map(|s| s.speed(base)).unwrap_or(base) as i32;
+
+        // Skip ahead to the first tick in which someone is ready
+        if !actors.join().any(|a| a.is_ready()) {
+            let ticks = (&entities, &actors)
+                .join()
+                .filter_map(|(e, a)| match speed_of(e, a.speed) {
+                    speed if speed > 0 => Some((speed - 1 - a.energy()) / speed),
+                    _ => None,
+                })
+                .min();
+
+            let ticks = match ticks {
+                Some(ticks) => ticks,
+                None => return,
+            };
+
+            for (e, actor) in (&entities, &mut actors).join() {
+                actor.gain(speed_of(e, actor.speed) * ticks);
             }
-            Turn::Others => {
-                if (&actors, !&players).join().any(|(a, _)| a.can_act()) {
-                    return;
-                }
-                for (actor, _) in (&mut actors, &players).join() {
-                    actor.refresh();
-                }
-                self.current = Turn::Player;
+
+            clock.ticks += ticks as u64;
+            stats.turns = clock.turn() as u32;
+        }
+
+        let mut ready = (&entities, &actors)
+            .join()
+            .filter(|(_, a)| a.is_ready())
+            .map(|(e, a)| (e, (a.energy(), speed_of(e, a.speed), players.contains(e))))
+            .collect::<Vec<_>>();
+        ready.sort_by_key(|&(_, key)| Reverse(key));
+
+        let next = match ready.iter().position(|&(e, _)| players.contains(e)) {
+            Some(0) => &ready[..1],
+            Some(player) => &ready[..player],
+            None => &ready[..],
+        };
+
+        for &(e, _) in next {
+            if let Some(actor) = actors.get_mut(e) {
+                actor.give_turn();
             }
         }
     }
@@ -62,8 +109,9 @@ impl<'s> System<'s> for TurnSystem {
 /// Resolves melee combat between units.
 ///
 /// For each defending unit, the system computes the actual damage that the entity will suffer
-/// based on its defense and the attacker's power. Damage calculation is not performed right away,
-/// rather the unit is simply tagged with the total amount of damage that it should take.
+/// based on its defense and the attacker's power, both including the bonuses of any equipment.
+/// Damage calculation is not performed right away, rather the unit is simply tagged
+/// with the total amount of damage that it should take.
 /// The [`DamageResolver`] handles the resolution of the damage itself.
 #[derive(SystemDesc)]
 pub struct MeleeCombatResolver;
@@ -74,6 +122,9 @@ impl<'s> System<'s> for MeleeCombatResolver {
         Entities<'s>,
         ReadStorage<'s, Name>,
         ReadStorage<'s, CombatStats>,
+        ReadStorage<'s, Equipped>,
+        ReadStorage<'s, MeleePowerBonus>,
+        ReadStorage<'s, DefenseBonus>,
         WriteStorage<'s, TargetedForMelee>,
         WriteStorage<'s, SuffersDamage>,
         Write<'s, CombatLog>,
@@ -81,8 +132,29 @@ impl<'s> System<'s> for MeleeCombatResolver {
 
     fn run(
         &mut self,
-        (entities, names, combat_stats, mut melee_targets, mut damage, mut log): Self::SystemData,
+        (
+            entities,
+            names,
+            combat_stats,
+            equipped,
+            power_bonuses,
+            defense_bonuses,
+            mut melee_targets,
+            mut damage,
+            mut log,
+        ): Self::SystemData,
     ) {
+        // Sum up the (power, defense) bonuses of everything each unit is wearing
+        let mut bonuses = HashMap::<Entity, (i32, i32)>::new();
+        for (Equipped { owner, .. }, power, defense) in
+            (&equipped, power_bonuses.maybe(), defense_bonuses.maybe()).join()
+        {
+            let bonus = bonuses.entry(*owner).or_default();
+            bonus.0 += power.map(|b| b.amount).unwrap_or(0);
+            bonus.1 += defense.map(|b| b.amount).unwrap_or(0);
+        }
+        let bonus = |e: Entity| bonuses.get(&e).copied().unwrap_or_default();
+
         let defenders = (&entities, &names, &combat_stats, melee_targets.drain());
 
         for (defender, Name(def_name), def_stats, TargetedForMelee { by: ref attackers }) in
@@ -92,11 +164,13 @@ impl<'s> System<'s> for MeleeCombatResolver {
                 let Name(atk_name) = names.get(*attacker).unwrap();
                 let atk_stats = combat_stats.get(*attacker).unwrap();
 
-                let dmg = i32::max(0, atk_stats.power - def_stats.defense);
+                let power = atk_stats.power + bonus(*attacker).0;
+                let defense = def_stats.defense + bonus(defender).1;
+                let dmg = i32::max(0, power - defense);
 
                 if dmg > 0 {
                     log.push(format!("{} hits {} for {} hp.", atk_name, def_name, dmg));
-                    SuffersDamage::damage(&mut damage, defender, dmg as u32);
+                    SuffersDamage::damage(&mut damage, defender, dmg as u32, *attacker);
                 } else {
                     log.push(format!("{} cannot hit {}.", atk_name, def_name));
                 }
@@ -109,29 +183,134 @@ impl<'s> System<'s> for MeleeCombatResolver {
 ///
 /// The system iterates over all the units with a pending [`SufferDamage`] component
 /// and subtracts the pending damage from their current HP. If a unit dies from the damage,
-/// its entity is killed and later deleted.
+/// its entity is killed and later deleted, and its killer is awarded experience for it.
+/// The player is never deleted, since the game over screen still needs it:
+/// it is up to the running game state to detect the player's death.
 #[derive(SystemDesc)]
 pub struct DamageResolver;
 
 impl<'s> System<'s> for DamageResolver {
+    #[allow(clippy::type_complexity)]
     type SystemData = (
         Entities<'s>,
+        ReadStorage<'s, Player>,
         ReadStorage<'s, Name>,
+        ReadStorage<'s, AwardsExperience>,
         WriteStorage<'s, SuffersDamage>,
         WriteStorage<'s, CombatStats>,
+        WriteStorage<'s, Experience>,
         Write<'s, CombatLog>,
+        Write<'s, RunStats>,
     );
 
-    fn run(&mut self, (entities, names, mut damages, mut combat_stats, mut log): Self::SystemData) {
-        let damageds = (&entities, &names, damages.drain(), &mut combat_stats);
+    fn run(
+        &mut self,
+        (
+            entities,
+            players,
+            names,
+            rewards,
+            mut damages,
+            mut combat_stats,
+            mut experience,
+            mut log,
+            mut run,
+        ): Self::SystemData,
+    ) {
+        let damageds = (&entities, damages.drain(), &mut combat_stats);
+
+        for (e, SuffersDamage { damage, by, cause }, ref mut stats) in damageds.join() {
+            if stats.hp <= 0 {
+                continue; // already dead
+            }
 
-        for (e, Name(name), SuffersDamage { damage }, ref mut stats) in damageds.join() {
-            stats.hp -= damage as i32;
+            // HP never go below zero, as the dead player is still around to be shown
+            stats.hp = i32::max(0, stats.hp - damage as i32);
 
             // If an entity drops below 0 HP, it dies
             if stats.hp <= 0 {
-                log.push(format!("{} is dead.", name));
-                entities.delete(e).unwrap();
+                // The last entity to deal damage gets credit for the kill
+                let killer = by.last().copied();
+
+                if players.contains(e) {
+                    log.push("You are dead.");
+                    run.killed_by = killer
+                        .and_then(|k| names.get(k))
+                        .map(|Name(n)| n.clone())
+                        .or(cause);
+                } else {
+                    if let Some(Name(name)) = names.get(e) {
+                        log.push(format!("{} is dead.", name));
+                    }
+                    if killer.map(|k| players.contains(k)).unwrap_or(false) {
+                        run.kills += 1;
+                    }
+                    if let (Some(exp), Some(AwardsExperience { amount })) =
+                        (killer.and_then(|k| experience.get_mut(k)), rewards.get(e))
+                    {
+                        exp.xp += amount;
+                    }
+                    entities.delete(e).unwrap();
+                }
+            }
+        }
+    }
+}
+
+/// Max HP gained at each level up.
+pub const LEVEL_UP_HP: i32 = 5;
+
+/// Power gained at each level up.
+pub const LEVEL_UP_POWER: i32 = 1;
+
+/// Defense gained at each level up.
+pub const LEVEL_UP_DEFENSE: i32 = 1;
+
+/// Levels up the units which have gathered enough experience.
+///
+/// Each level up raises the unit's combat stats and fully heals it. When the player
+/// levels up, the new level is signaled through the [`LevelUp`] resource,
+/// so that the running game state can congratulate them.
+#[derive(SystemDesc)]
+pub struct LevelUpSystem;
+
+impl<'s> System<'s> for LevelUpSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'s>,
+        ReadStorage<'s, Player>,
+        WriteStorage<'s, Experience>,
+        WriteStorage<'s, CombatStats>,
+        Write<'s, LevelUp>,
+        Write<'s, CombatLog>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, players, mut experience, mut combat_stats, mut level_up, mut log): Self::SystemData,
+    ) {
+        for (e, exp, stats) in (&entities, &mut experience, &mut combat_stats).join() {
+            // Dead units do not get to enjoy their last kill
+            if stats.hp <= 0 {
+                continue;
+            }
+
+            while exp.xp >= exp.next_level() {
+                exp.xp -= exp.next_level();
+                exp.level += 1;
+
+                stats.max_hp += LEVEL_UP_HP;
+                stats.hp = stats.max_hp;
+                stats.power += LEVEL_UP_POWER;
+                stats.defense += LEVEL_UP_DEFENSE;
+
+                if players.contains(e) {
+                    log.push(format!(
+                        "You feel stronger! Welcome to level {}.",
+                        exp.level
+                    ));
+                    level_up.0 = Some(exp.level);
+                }
             }
         }
     }
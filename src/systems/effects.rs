@@ -0,0 +1,111 @@
+//! This module contains all the systems related to status effects.
+
+use crate::{components::*, resources::CombatLog, systems::GameClock};
+
+use amethyst::{
+    core::Hidden,
+    derive::SystemDesc,
+    ecs::{Entities, Join, Read, ReadStorage, System, SystemData, Write, WriteStorage},
+};
+
+/// Applies the status effects of all the entities, once per turn.
+///
+/// The system runs right after the [`TurnSystem`], and whenever the [`GameClock`] reaches
+/// a new turn, every entity goes through its effects: poison hurts it and regeneration heals it.
+/// Effects on speed are taken care of by the [`TurnSystem`] itself.
+/// Each effect then lasts one turn less, and is removed once it runs out.
+///
+/// Turns are measured by the clock rather than by the entity's own actions,
+/// so that effects last just as long for fast and slow entities alike.
+///
+/// [`TurnSystem`]: super::TurnSystem
+#[derive(Default, SystemDesc)]
+#[system_desc(name(StatusEffectSystemDesc))]
+pub struct StatusEffectSystem {
+    #[system_desc(skip)]
+    last_turn: Option<u64>,
+}
+
+impl<'s> System<'s> for StatusEffectSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'s>,
+        ReadStorage<'s, Player>,
+        ReadStorage<'s, Name>,
+        ReadStorage<'s, Hidden>,
+        WriteStorage<'s, StatusEffects>,
+        WriteStorage<'s, CombatStats>,
+        WriteStorage<'s, SuffersDamage>,
+        Read<'s, GameClock>,
+        Write<'s, CombatLog>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            players,
+            names,
+            hiddens,
+            mut statuses,
+            mut stats,
+            mut damage,
+            clock,
+            mut log,
+        ): Self::SystemData,
+    ) {
+        // Effects only tick when a new turn begins, possibly more than once if time flew
+        let turn = clock.turn();
+        let elapsed = match self.last_turn.replace(turn) {
+            Some(last) if last < turn => (turn - last) as u32,
+            _ => return,
+        };
+
+        let mut cured = Vec::new();
+
+        for (e, StatusEffects(effects)) in (&entities, &mut statuses).join() {
+            let is_player = players.contains(e);
+
+            for active in effects.iter_mut() {
+                let ticks = u32::min(elapsed, active.turns);
+
+                match active.effect {
+                    StatusEffect::Poison(amount) => {
+                        let amount = amount as u32 * ticks;
+                        SuffersDamage::hazard(&mut damage, e, amount, "poison");
+                        if is_player {
+                            log.push("The poison burns in your veins.");
+                        }
+                    }
+                    StatusEffect::Regeneration(amount) => {
+                        if let Some(stats) = stats.get_mut(e) {
+                            stats.hp = i32::min(stats.max_hp, stats.hp + amount * ticks as i32);
+                        }
+                    }
+                    StatusEffect::Confusion | StatusEffect::Haste | StatusEffect::Slow => (),
+                }
+
+                active.turns -= ticks;
+            }
+
+            // Let the player know about the effects wearing off, as long as they can see it
+            for active in effects.iter().filter(|active| active.turns == 0) {
+                let what = active.effect.name().to_lowercase();
+                if is_player {
+                    log.push(format!("You are no longer {}.", what));
+                } else if let (Some(Name(name)), false) = (names.get(e), hiddens.contains(e)) {
+                    log.push(format!("{} is no longer {}.", name, what));
+                }
+            }
+
+            effects.retain(|active| active.turns > 0);
+            if effects.is_empty() {
+                cured.push(e);
+            }
+        }
+
+        for e in cured {
+            statuses.remove(e);
+        }
+    }
+}
@@ -1,14 +1,19 @@
 //! This module contains all the input-related systems.
 
 use crate::{
-    components::{ActsOnTurns, Pickable, Player, Position, WantsToMove, WantsToPickUp},
+    components::{
+        Action, ActsOnTurns, AutoMove, AutoMoveGoal, CombatStats, Pickable, Player, Position,
+        Viewshed, WantsToMove, WantsToPickUp,
+    },
+    core::map::{TileKind, WorldMap},
+    graphics::renderer,
     math::Point,
-    resources::CombatLog,
-    states::{GameStateWrapper, GameTrans, Intent, InventoryState},
+    resources::{CombatLog, LevelTransition},
+    states::{GameStateWrapper, GameTrans, Intent, InventoryState, LookState, TravelState},
 };
 
 use amethyst::{
-    ecs::{Entities, Entity, Join, ReadStorage, World, Write, WriteStorage},
+    ecs::{Entities, Entity, Join, Read, ReadStorage, World, Write, WriteStorage},
     input::BindingTypes,
     prelude::*,
 };
@@ -19,6 +24,24 @@ use std::fmt;
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction { N, W, S, E, NW, SW, SE, NE }
 
+impl Direction {
+    /// Returns the `(x, y)` offset of a single step in this direction.
+    pub fn delta(self) -> (i32, i32) {
+        use Direction::*;
+
+        match self {
+            N => (0, 1),
+            W => (-1, 0),
+            S => (0, -1),
+            E => (1, 0),
+            NW => (-1, 1),
+            SW => (-1, -1),
+            SE => (1, -1),
+            NE => (1, 1),
+        }
+    }
+}
+
 /// Stub implementation for axis bindings. Not actually used right now.
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AxisBindings;
@@ -36,6 +59,16 @@ pub enum ActionBinding {
     PickUp,
     OpenInventory,
     DropItem,
+    Equip,
+    Descend,
+    Ascend,
+    CloseDoor,
+    Explore,
+    Travel,
+    Click,
+    Look,
+    SaveGame,
+    Confirm,
     Cancel,
 }
 
@@ -67,34 +100,115 @@ type RunStateSystemData<'s> = (
     ReadStorage<'s, Player>,
     ReadStorage<'s, Position>,
     ReadStorage<'s, Pickable>,
+    ReadStorage<'s, CombatStats>,
     WriteStorage<'s, ActsOnTurns>,
+    WriteStorage<'s, AutoMove>,
     WriteStorage<'s, WantsToMove>,
     WriteStorage<'s, WantsToPickUp>,
+    WriteStorage<'s, Viewshed>,
+    Write<'s, WorldMap>,
+    Write<'s, LevelTransition>,
     Write<'s, CombatLog>,
 );
 
 impl RunStateInputDispatcher {
     pub fn handle(&mut self, world: &mut World, con: Entity, action: ActionBinding) -> GameTrans {
-        let (entities, players, positions, pickables, mut actors, mut movers, mut pickers, mut log) =
-            world.system_data::<RunStateSystemData>();
+        let (
+            entities,
+            players,
+            positions,
+            pickables,
+            combat_stats,
+            mut actors,
+            mut auto_moves,
+            mut movers,
+            mut pickers,
+            mut viewsheds,
+            mut map,
+            mut transition,
+            mut log,
+        ) = world.system_data::<RunStateSystemData>();
 
-        if let Some((player, actor, &Position(p), _)) =
-            (&entities, &mut actors, &positions, &players).join().next()
+        if let Some((player, actor, &Position(p), stats, _)) =
+            (&entities, &mut actors, &positions, &combat_stats, &players)
+                .join()
+                .next()
         {
-            if !actor.perform() {
+            // Any key stops the player from moving on its own
+            if auto_moves.remove(player).is_some() {
+                log.push("You stop.");
                 return Trans::None;
             }
 
+            // Looking around, exploring and travelling do not take a turn by themselves
             match action {
-                ActionBinding::Move(d) => move_player(player, p, d, &mut movers),
-                ActionBinding::PickUp => pickup_item(
-                    player,
-                    &entities,
-                    &pickables,
-                    &positions,
-                    &mut pickers,
-                    &mut log,
-                ),
+                ActionBinding::Look => {
+                    return Trans::Push(Box::new(GameStateWrapper::new(LookState::new(p, con))));
+                }
+                ActionBinding::Explore => {
+                    auto_moves
+                        .insert(
+                            player,
+                            AutoMove {
+                                goal: AutoMoveGoal::Explore,
+                                hp: stats.hp,
+                                from: p,
+                            },
+                        )
+                        .unwrap();
+                    return Trans::None;
+                }
+                ActionBinding::Travel => {
+                    return Trans::Push(Box::new(GameStateWrapper::new(TravelState::new(p, con))));
+                }
+                ActionBinding::Click => {
+                    return match renderer::mouse_to_map(world, con) {
+                        Some(to) if map.contains(to) && map[to].revealed => {
+                            Trans::Push(Box::new(GameStateWrapper::new(TravelState::new(to, con))))
+                        }
+                        _ => Trans::None,
+                    };
+                }
+                _ => (),
+            }
+
+            if !actor.can_act() {
+                return Trans::None;
+            }
+
+            // Actions end the turn as long as they do anything, except for browsing
+            // the inventory: only picking an item does
+            let performed = match action {
+                ActionBinding::Move(d) => {
+                    let to = p + d.delta();
+                    move_player(player, to, &mut movers);
+
+                    if (&positions, &combat_stats)
+                        .join()
+                        .any(|(&Position(p2), _)| p2 == to)
+                    {
+                        Some(Action::Attack)
+                    } else if map[to].kind == TileKind::ClosedDoor {
+                        Some(Action::Interact)
+                    } else {
+                        Some(Action::Move)
+                    }
+                }
+                ActionBinding::PickUp => {
+                    let picked = pickup_item(
+                        player,
+                        &entities,
+                        &pickables,
+                        &positions,
+                        &mut pickers,
+                        &mut log,
+                    );
+                    if picked {
+                        Some(Action::PickUp)
+                    } else {
+                        None
+                    }
+                }
                 ActionBinding::OpenInventory => {
                     return Trans::Push(Box::new(GameStateWrapper::new(InventoryState::new(
                         Intent::UseItem,
@@ -107,7 +221,42 @@ impl RunStateInputDispatcher {
                         con,
                     ))));
                 }
-                _ => (),
+                ActionBinding::Equip => {
+                    return Trans::Push(Box::new(GameStateWrapper::new(InventoryState::new(
+                        Intent::Equip,
+                        con,
+                    ))));
+                }
+                ActionBinding::Descend => {
+                    if map[p].kind == TileKind::DownStairs {
+                        *transition = LevelTransition::Descend;
+                        Some(Action::Interact)
+                    } else {
+                        log.push("There is no way down from here.");
+                        None
+                    }
+                }
+                ActionBinding::Ascend => {
+                    if map[p].kind == TileKind::UpStairs {
+                        *transition = LevelTransition::Ascend;
+                        Some(Action::Interact)
+                    } else {
+                        log.push("There is no way up from here.");
+                        None
+                    }
+                }
+                ActionBinding::CloseDoor => {
+                    if close_door(p, &mut map, &mut viewsheds, &mut log) {
+                        Some(Action::Interact)
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            };
+
+            if let Some(performed) = performed {
+                actor.perform(performed);
             }
         }
 
@@ -115,28 +264,37 @@ impl RunStateInputDispatcher {
     }
 }
 
-fn move_player(
-    player: Entity,
+fn move_player(player: Entity, to: Point, movers: &mut WriteStorage<WantsToMove>) {
+    movers.insert(player, WantsToMove { to }).unwrap();
+}
+
+fn close_door(
     from: Point,
-    dir: Direction,
-    movers: &mut WriteStorage<WantsToMove>,
-) {
-    use Direction::*;
-
-    let delta = match dir {
-        N => (0, 1),
-        W => (-1, 0),
-        S => (0, -1),
-        E => (1, 0),
-        NW => (-1, 1),
-        SW => (-1, -1),
-        SE => (1, -1),
-        NE => (1, 1),
-    };
-
-    movers
-        .insert(player, WantsToMove { to: from + delta })
-        .unwrap();
+    map: &mut WorldMap,
+    viewsheds: &mut WriteStorage<Viewshed>,
+    log: &mut Write<CombatLog>,
+) -> bool {
+    // Doors with someone standing in them cannot be closed
+    let door = map
+        .get_adjacent_exits(from)
+        .into_iter()
+        .find(|&p| map[p].kind == TileKind::OpenDoor);
+
+    if let Some(p) = door {
+        map[p].kind = TileKind::ClosedDoor;
+        map[p].blocked = true;
+
+        // The door might now be blocking anyone's sight
+        for vs in viewsheds.join() {
+            vs.dirty = true;
+        }
+
+        log.push("You close the door.");
+        true
+    } else {
+        log.push("There is no open door nearby.");
+        false
+    }
 }
 
 fn pickup_item(
@@ -146,7 +304,7 @@ fn pickup_item(
     positions: &ReadStorage<Position>,
     pickers: &mut WriteStorage<WantsToPickUp>,
     log: &mut Write<CombatLog>,
-) {
+) -> bool {
     if let Some(&Position(p)) = positions.get(player) {
         let target_item = (entities, pickables, positions)
             .join()
@@ -163,8 +321,11 @@ fn pickup_item(
 
         if let Some(what) = target_item {
             pickers.insert(player, WantsToPickUp { what }).unwrap();
-        } else {
-            log.push("There is nothing here to pick up.");
+            return true;
         }
+
+        log.push("There is nothing here to pick up.");
     }
+
+    false
 }
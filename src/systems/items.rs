@@ -47,29 +47,97 @@ impl<'s> System<'s> for ItemUsageResolver {
     type SystemData = (
         Entities<'s>,
         ReadStorage<'s, Name>,
+        ReadStorage<'s, Position>,
         ReadStorage<'s, Consumable>,
         ReadStorage<'s, HealsUser>,
+        ReadStorage<'s, InflictsDamage>,
+        ReadStorage<'s, AppliesEffect>,
         WriteStorage<'s, WantsToUseItem>,
         WriteStorage<'s, CombatStats>,
+        WriteStorage<'s, SuffersDamage>,
+        WriteStorage<'s, StatusEffects>,
         Write<'s, CombatLog>,
     );
 
     fn run(
         &mut self,
-        (entities, names, consumables, healing, mut users, mut stats, mut log): Self::SystemData,
+        (
+            entities,
+            names,
+            positions,
+            consumables,
+            healing,
+            damaging,
+            affecting,
+            mut users,
+            mut stats,
+            mut damage,
+            mut statuses,
+            mut log,
+        ): Self::SystemData,
     ) {
-        for (who, WantsToUseItem { what }) in (&entities, users.drain()).join() {
+        for (who, WantsToUseItem { what, target }) in (&entities, users.drain()).join() {
+            let item_name = names.get(what).map(|Name(n)| n.as_str()).unwrap_or("item");
+
             // Healing item used by a unit with combat stats -> heal unit
             if let (Some(stats), Some(HealsUser { amount })) =
                 (&mut stats.get_mut(who), healing.get(what))
             {
                 stats.hp = i32::min(stats.max_hp, stats.hp + amount);
 
-                log.push(format!(
-                    "You use the {}, healing {} hp.",
-                    names.get(what).map(|Name(n)| n.as_str()).unwrap_or("item"),
-                    amount
-                ))
+                log.push(format!("You use the {}, healing {} hp.", item_name, amount))
+            }
+
+            // Damaging item used on a location -> damage every fighter standing there
+            if let (Some(target), Some(InflictsDamage { amount })) = (target, damaging.get(what)) {
+                let mut hit = false;
+
+                for (victim, &Position(p), _) in (&entities, &positions, &stats).join() {
+                    if p == target {
+                        log.push(format!(
+                            "The {} hits {} for {} hp.",
+                            item_name,
+                            names.get(victim).map(|Name(n)| n.as_str()).unwrap_or("it"),
+                            amount
+                        ));
+                        SuffersDamage::damage(&mut damage, victim, *amount as u32, who);
+                        hit = true;
+                    }
+                }
+
+                if !hit {
+                    log.push(format!("The {} hits nothing.", item_name));
+                }
+            }
+
+            // Effect item used on a location -> affect every fighter standing there
+            // Effect item used on no location -> affect the user
+            if let Some(&AppliesEffect { effect, turns }) = affecting.get(what) {
+                let status = effect.name().to_lowercase();
+
+                if let Some(target) = target {
+                    let victims = (&entities, &positions, &stats)
+                        .join()
+                        .filter(|(_, &Position(p), _)| p == target)
+                        .map(|(victim, _, _)| victim)
+                        .collect::<Vec<_>>();
+
+                    if victims.is_empty() {
+                        log.push(format!("The {} affects nothing.", item_name));
+                    }
+
+                    for victim in victims {
+                        StatusEffects::apply(&mut statuses, victim, effect, turns);
+                        log.push(format!(
+                            "{} is {}.",
+                            names.get(victim).map(|Name(n)| n.as_str()).unwrap_or("It"),
+                            status
+                        ));
+                    }
+                } else {
+                    StatusEffects::apply(&mut statuses, who, effect, turns);
+                    log.push(format!("You use the {}, and are {}.", item_name, status));
+                }
             }
 
             if consumables.contains(what) {
@@ -79,6 +147,66 @@ impl<'s> System<'s> for ItemUsageResolver {
     }
 }
 
+/// System that resolves an entity's intent to equip or take off an item.
+///
+/// Equipping an item in a slot which is already taken swaps the two items,
+/// sending the old one back to the backpack.
+#[derive(SystemDesc)]
+pub struct EquipResolver;
+
+impl<'s> System<'s> for EquipResolver {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'s>,
+        ReadStorage<'s, Name>,
+        ReadStorage<'s, Equippable>,
+        WriteStorage<'s, WantsToEquip>,
+        WriteStorage<'s, WantsToUnequip>,
+        WriteStorage<'s, Equipped>,
+        Write<'s, CombatLog>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, names, equippables, mut equippers, mut unequippers, mut equipped, mut log): Self::SystemData,
+    ) {
+        let item_name = |item| names.get(item).map(|Name(n)| n.as_str()).unwrap_or("item");
+
+        for (_, WantsToUnequip { what }) in (&entities, unequippers.drain()).join() {
+            if equipped.remove(what).is_some() {
+                log.push(format!("You take off the {}.", item_name(what)));
+            }
+        }
+
+        for (who, WantsToEquip { what }) in (&entities, equippers.drain()).join() {
+            let slot = match equippables.get(what) {
+                Some(Equippable { slot }) => *slot,
+                None => {
+                    log.push(format!("You cannot equip the {}.", item_name(what)));
+                    continue;
+                }
+            };
+
+            // Free the slot first
+            let worn = (&entities, &equipped)
+                .join()
+                .filter(|(_, e)| e.owner == who && e.slot == slot)
+                .map(|(item, _)| item)
+                .collect::<Vec<_>>();
+
+            for item in worn {
+                equipped.remove(item);
+                log.push(format!("You take off the {}.", item_name(item)));
+            }
+
+            equipped
+                .insert(what, Equipped { owner: who, slot })
+                .unwrap();
+            log.push(format!("You equip the {}.", item_name(what)));
+        }
+    }
+}
+
 /// System that resolve's an entity's intent to drop an item.
 #[derive(SystemDesc)]
 pub struct ItemDropResolver;
@@ -90,13 +218,14 @@ impl<'s> System<'s> for ItemDropResolver {
         WriteStorage<'s, WantsToDropItem>,
         WriteStorage<'s, Position>,
         WriteStorage<'s, InBackpack>,
+        WriteStorage<'s, Equipped>,
         WriteStorage<'s, Hidden>,
         Write<'s, CombatLog>,
     );
 
     fn run(
         &mut self,
-        (names, mut droppers, mut positions, mut carried, mut hiddens, mut log): Self::SystemData,
+        (names, mut droppers, mut positions, mut carried, mut equipped, mut hiddens, mut log): Self::SystemData,
     ) {
         let locations = (&positions, droppers.drain())
             .join()
@@ -105,6 +234,7 @@ impl<'s> System<'s> for ItemDropResolver {
 
         for (what, whereto) in locations {
             carried.remove(what).unwrap();
+            equipped.remove(what); // dropped items are taken off first
             hiddens.remove(what).unwrap();
             positions.insert(what, Position(whereto)).unwrap();
 
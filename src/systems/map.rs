@@ -2,17 +2,18 @@
 
 use crate::{
     components::*,
-    core::map::{ShadowcastFoV, WorldMap},
+    core::map::{ShadowcastFoV, TileKind, WorldMap},
     math::Point,
-    resources::TileDimension,
+    resources::{CombatLog, SeededRng, TileDimension},
 };
 
 use amethyst::{
     core::{transform::Transform, Hidden},
     derive::SystemDesc,
-    ecs::{Entities, Join, Read, ReadStorage, System, SystemData, Write, WriteStorage},
+    ecs::{Entities, Entity, Join, Read, ReadStorage, System, SystemData, Write, WriteStorage},
     renderer::SpriteRender,
 };
+use rand::seq::SliceRandom;
 
 /// Refreshes the map's internal index.
 ///
@@ -89,7 +90,14 @@ impl<'s> System<'s> for VisibilitySystem {
     }
 }
 
+/// Damage suffered by entities walking into lava.
+const LAVA_DAMAGE: u32 = 5;
+
 /// System that manages entities that want to move in this turn.
+///
+/// Besides moving entities around, this also applies the effects of the terrain
+/// they walk onto, and makes them spend longer on the move the harder it is to walk.
+/// Confused entities end up moving in a random direction.
 #[derive(SystemDesc)]
 pub struct MoveResolver;
 
@@ -102,6 +110,22 @@ impl MoveResolver {
         }
         *from = to;
     }
+
+    fn on_enter(
+        &self,
+        who: Entity,
+        kind: TileKind,
+        is_player: bool,
+        damage: &mut WriteStorage<SuffersDamage>,
+        log: &mut CombatLog,
+    ) {
+        if kind == TileKind::Lava {
+            SuffersDamage::hazard(damage, who, LAVA_DAMAGE, "lava");
+            if is_player {
+                log.push("The lava burns you!");
+            }
+        }
+    }
 }
 
 impl<'s> System<'s> for MoveResolver {
@@ -112,12 +136,18 @@ impl<'s> System<'s> for MoveResolver {
         ReadStorage<'s, Faction>,
         ReadStorage<'s, CombatStats>,
         ReadStorage<'s, BlocksTile>,
+        ReadStorage<'s, OpensDoors>,
+        ReadStorage<'s, StatusEffects>,
         WriteStorage<'s, Position>,
         WriteStorage<'s, WantsToMove>,
         WriteStorage<'s, TargetedForMelee>,
         WriteStorage<'s, Viewshed>,
+        WriteStorage<'s, SuffersDamage>,
+        WriteStorage<'s, ActsOnTurns>,
         Write<'s, Point>,
         Write<'s, WorldMap>,
+        Write<'s, CombatLog>,
+        Write<'s, SeededRng>,
     );
 
     fn run(
@@ -128,15 +158,38 @@ impl<'s> System<'s> for MoveResolver {
             factions,
             combatants,
             blockers,
+            door_openers,
+            statuses,
             mut positions,
             mut movers,
             mut melee_targets,
             mut viewsheds,
+            mut damage,
+            mut actors,
             mut ppos,
             mut map,
+            mut log,
+            mut rng,
         ): Self::SystemData,
     ) {
         for (e1, WantsToMove { to }) in (&entitites, movers.drain()).join() {
+            let to = match statuses.get(e1) {
+                Some(s) if s.is_confused() => {
+                    let from = positions.get(e1).map(|&Position(p)| p).unwrap_or(to);
+                    let exits = map.get_adjacent_exits(from);
+                    match exits.choose(&mut *rng) {
+                        Some(&stumble) => {
+                            if players.contains(e1) && stumble != to {
+                                log.push("You stumble around in confusion.");
+                            }
+                            stumble
+                        }
+                        None => to,
+                    }
+                }
+                _ => to,
+            };
+
             if !map[to].blocked {
                 if let Some(Position(from)) = positions.get_mut(e1) {
                     self.move_entity(&mut map, from, to, blockers.contains(e1)); // update map state
@@ -150,6 +203,24 @@ impl<'s> System<'s> for MoveResolver {
                     if players.contains(e1) {
                         *ppos = to;
                     }
+
+                    let kind = map[to].kind;
+                    self.on_enter(e1, kind, players.contains(e1), &mut damage, &mut log);
+
+                    // The move itself was paid for when deciding it, the terrain is paid on top
+                    let extra = kind.walk_cost().unwrap_or(1) - 1;
+                    ActsOnTurns::spend_extra(&mut actors, e1, Action::Move, extra);
+                }
+            } else if map[to].kind == TileKind::ClosedDoor {
+                // Walking into a closed door opens it, if the entity is able to
+                if door_openers.contains(e1) {
+                    map[to].kind = TileKind::OpenDoor;
+                    map[to].blocked = false;
+
+                    // The door might have been blocking anyone's sight
+                    for vs in (&mut viewsheds).join() {
+                        vs.dirty = true;
+                    }
                 }
             } else {
                 let victims = (&entitites, &factions, &positions, &combatants);
@@ -201,3 +272,57 @@ impl<'s> System<'s> for PositionTranslator {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::ecs::{Builder, RunNow, World, WorldExt};
+
+    // Moves an entity from a floor tile onto a tile of the given kind, returning its energy.
+    fn energy_after_moving_onto(kind: TileKind) -> i32 {
+        let mut world = World::new();
+        let mut resolver = MoveResolver;
+        resolver.setup(&mut world);
+
+        let mut map = WorldMap::new(3, 1);
+        map[Point::new(0, 0)].kind = TileKind::Floor;
+        map[Point::new(1, 0)].kind = kind;
+        world.insert(map);
+
+        let mut actor = ActsOnTurns::default();
+        actor.give_turn();
+        actor.perform(Action::Move);
+
+        let mover = world
+            .create_entity()
+            .with(Position(Point::new(0, 0)))
+            .with(WantsToMove {
+                to: Point::new(1, 0),
+            })
+            .with(actor)
+            .build();
+
+        resolver.run_now(&world);
+
+        let positions = world.read_storage::<Position>();
+        assert_eq!(
+            positions.get(mover).map(|&Position(p)| p),
+            Some(Point::new(1, 0))
+        );
+
+        world
+            .read_storage::<ActsOnTurns>()
+            .get(mover)
+            .unwrap()
+            .energy()
+    }
+
+    #[test]
+    fn moving_through_water_costs_more_than_floor() {
+        let floor = energy_after_moving_onto(TileKind::Floor);
+        let water = energy_after_moving_onto(TileKind::ShallowWater);
+
+        assert_eq!(floor, -Action::Move.cost());
+        assert!(water < floor);
+    }
+}
@@ -4,6 +4,7 @@
 
 mod ai;
 mod combat;
+mod effects;
 mod input;
 mod items;
 mod map;
@@ -11,6 +12,7 @@ mod map;
 // Re-export all modules
 pub use ai::*;
 pub use combat::*;
+pub use effects::*;
 pub use input::*;
 pub use items::*;
 pub use map::*;